@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+// Linux BTN_LEFT, ydotool button codes are offsets from it
+const BTN_LEFT: u32 = 0x110;
+
+/// Buttons by name, in the order of their codes from BTN_LEFT
+const BUTTONS: &[&str] = &[
+    "left", "right", "middle", "side", "extra", "forward", "back", "task",
+];
+
+/// Ranges of Linux event codes that are buttons rather than keys
+const BUTTON_CODES: &[std::ops::RangeInclusive<u32>] = &[0x100..=0x15f, 0x2c0..=0x2e7];
+
+/// Looks up a button like `left`, `BTN_MIDDLE` or a raw button code in decimal or with a `0x` prefix
+#[must_use]
+pub fn button_code(name: &str) -> Option<u32> {
+    let button = name
+        .get(..4)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("BTN_"))
+        .map_or(name, |_| &name[4..]);
+    if let Some(i) = BUTTONS
+        .iter()
+        .position(|known| known.eq_ignore_ascii_case(button))
+    {
+        return Some(BTN_LEFT + i as u32);
+    }
+
+    match name.strip_prefix("0x").or_else(|| name.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => name.parse().ok(),
+    }
+    .filter(|code| BUTTON_CODES.iter().any(|codes| codes.contains(code)))
+}
+
+/// Name of a button for [`button_code`], its raw code in hex if it has no short name
+#[must_use]
+pub fn button_name(code: u32) -> String {
+    code.checked_sub(BTN_LEFT)
+        .and_then(|i| BUTTONS.get(i as usize))
+        .map_or_else(|| format!("{code:#x}"), ToString::to_string)
+}
+
+/// Mouse click, which can be given in ydotool's byte encoding: the low nibble selects the button,
+/// 0x40 presses it and 0x80 releases it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Click {
+    /// Linux input event code of the button
+    pub button: u32,
+    pub press: bool,
+    pub release: bool,
+}
+
+impl Click {
+    #[must_use]
+    pub const fn from_byte(byte: u8) -> Self {
+        Self {
+            button: (byte & 0x0f) as u32 | BTN_LEFT,
+            press: byte & 0x40 != 0,
+            release: byte & 0x80 != 0,
+        }
+    }
+
+    /// Byte encoding of the click, `None` for buttons outside the 16 it can address
+    #[must_use]
+    pub const fn to_byte(&self) -> Option<u8> {
+        if self.button < BTN_LEFT || self.button > BTN_LEFT | 0x0f {
+            return None;
+        }
+
+        let mut byte = (self.button - BTN_LEFT) as u8;
+        if self.press {
+            byte |= 0x40;
+        }
+        if self.release {
+            byte |= 0x80;
+        }
+
+        Some(byte)
+    }
+}
+
+impl FromStr for Click {
+    type Err = String;
+
+    /// Parses a click byte written in hex with a `0x` prefix or in decimal,
+    /// or a button as `<button>[:<state>]` where state is `1`/`down` or `0`/`up` and without one it's clicked
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (button, press, release) = match s.rsplit_once(':') {
+            Some((button, "1" | "down")) => (button, true, false),
+            Some((button, "0" | "up")) => (button, false, true),
+            Some((_, state)) => {
+                return Err(format!(
+                    "Pressed state must be 0, 1, down or up, got '{state}'"
+                ));
+            }
+            None => {
+                let byte = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                    Some(hex) => u8::from_str_radix(hex, 16).ok(),
+                    None => s.parse::<u8>().ok(),
+                };
+                if let Some(byte) = byte {
+                    return Ok(Self::from_byte(byte));
+                }
+
+                (s, true, true)
+            }
+        };
+
+        let button = button_code(button).ok_or_else(|| {
+            format!("Invalid click '{s}': expected a click byte like 0xC0 or a button like `left`")
+        })?;
+
+        Ok(Self {
+            button,
+            press,
+            release,
+        })
+    }
+}
@@ -1,7 +1,8 @@
 use clap::Parser;
-use libwhydotool::KeyPress;
-use std::num::ParseIntError;
-use xkbcommon::xkb;
+#[cfg(feature = "portals")]
+use clap::{Args, Subcommand};
+use libwhydotool::{Click, Coordinate, Easing, Key, ScrollSource, button_code, parse_chord};
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "whydotool")]
@@ -20,12 +21,30 @@ pub struct Cli {
     pub force_portal: bool,
 }
 
-#[derive(Parser, Debug)]
+impl Cli {
+    /// Whether flags changing how devices are created were given
+    pub const fn device_flags(&self) -> bool {
+        #[cfg(feature = "portals")]
+        let device_flags = self.force_portal;
+        #[cfg(not(feature = "portals"))]
+        let device_flags = false;
+
+        device_flags
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
 pub enum Commands {
     Click {
-        /// Buttons to click (hex values like 0xC0 for left click)
+        /// Buttons to click as `<button>[:<state>]`, where button is a name (`left`, `right`, `middle`, `side`,
+        /// `extra`, `forward`, `back`, `task`), `BTN_*` name or raw code (`0x113`) and state is `1`/`down` or `0`/`up`;
+        /// without a state the button is clicked. ydotool's bytes like `0xC0` for a left click work too
         #[arg(num_args = 1..)]
-        buttons: Vec<String>,
+        buttons: Vec<Click>,
+
+        /// Click each button N times in a row, e.g. 2 for a double click
+        #[arg(short = 'c', long = "count", default_value_t = 1)]
+        count: u32,
 
         /// Repeat the sequence N times
         #[arg(short = 'r', long = "repeat", default_value_t = 1)]
@@ -37,7 +56,7 @@ pub enum Commands {
     },
     Mousemove {
         /// Move mouse wheel relatively
-        #[arg(short = 'w', long = "wheel")]
+        #[arg(short = 'w', long = "wheel", conflicts_with = "absolute")]
         wheel: bool,
 
         /// Use absolute position, not applicable to wheel.
@@ -45,13 +64,106 @@ pub enum Commands {
         #[arg(short = 'a', long = "absolute")]
         absolute: bool,
 
-        /// X position
+        /// X position, absolute ones can also be a percentage of the width like `50%`
         #[arg(short = 'x', long = "xpos", allow_hyphen_values = true)]
-        xpos: f64,
+        xpos: Coordinate,
 
-        /// Y position
+        /// Y position, absolute ones can also be a percentage of the height like `50%`
         #[arg(short = 'y', long = "ypos", allow_hyphen_values = true)]
-        ypos: f64,
+        ypos: Coordinate,
+
+        /// Output the absolute position is relative to, e.g. `DP-2`
+        #[arg(short = 'o', long = "output", requires = "absolute")]
+        output: Option<String>,
+
+        /// Move gradually over a duration like `300ms` or `1.5s`, instead of jumping
+        #[arg(long = "duration", value_parser = parse_duration, conflicts_with = "wheel")]
+        duration: Option<Duration>,
+
+        /// Absolute position a gradual move starts at as `X,Y`, needed as the pointer's own isn't known
+        #[arg(long = "from", value_parser = parse_position, requires_all = ["absolute", "duration"])]
+        from: Option<(Coordinate, Coordinate)>,
+
+        /// Number of motion events of a gradual move, one per 8ms by default
+        #[arg(long = "steps", requires = "duration")]
+        steps: Option<u32>,
+
+        /// Easing of a gradual move: linear, ease-in, ease-out or ease-in-out
+        #[arg(long = "easing", default_value = "linear", requires = "duration")]
+        easing: Easing,
+
+        /// Bend a gradual move into a Bezier curve through `X,Y`, relative to the start. Can be repeated
+        #[arg(long = "control", value_parser = parse_point, allow_hyphen_values = true, requires = "duration")]
+        controls: Vec<(f64, f64)>,
+    },
+    /// Touch the screen through the portal, positions can be percentages of the output like `50%,50%`
+    #[cfg(feature = "portals")]
+    Touch {
+        #[command(subcommand)]
+        gesture: TouchGesture,
+
+        /// Output the positions are on, e.g. `DP-2`, the first one shared by default
+        #[arg(short = 'o', long = "output", global = true)]
+        output: Option<String>,
+    },
+    /// Scroll by wheel clicks, or by a distance as a touchpad or other continuous source would
+    Scroll {
+        /// Wheel clicks to scroll, positive ones scroll down (or right) and negative ones up (or left)
+        #[arg(
+            short = 'c',
+            long = "clicks",
+            allow_hyphen_values = true,
+            conflicts_with_all = ["xpos", "ypos", "source"],
+            required_unless_present_any = ["xpos", "ypos"]
+        )]
+        clicks: Option<i32>,
+
+        /// Scroll the clicks horizontally
+        #[arg(long = "horizontal", requires = "clicks")]
+        horizontal: bool,
+
+        /// Horizontal distance, positive scrolls right
+        #[arg(short = 'x', long = "xpos", allow_hyphen_values = true)]
+        xpos: Option<f64>,
+
+        /// Vertical distance, positive scrolls down
+        #[arg(short = 'y', long = "ypos", allow_hyphen_values = true)]
+        ypos: Option<f64>,
+
+        /// Source of the distance: wheel, finger or continuous.
+        /// Finger scrolls end as if the fingers were lifted, letting clients scroll on kinetically
+        #[arg(long = "source", default_value = "continuous")]
+        source: ScrollSource,
+    },
+    /// Press a button at one absolute position, move gradually to another and release it there
+    Drag {
+        /// Absolute position to press at as `X,Y`, either can be a percentage like `50%`
+        #[arg(long = "from", value_parser = parse_position)]
+        from: (Coordinate, Coordinate),
+
+        /// Absolute position to release at as `X,Y`, either can be a percentage like `50%`
+        #[arg(long = "to", value_parser = parse_position)]
+        to: (Coordinate, Coordinate),
+
+        /// Output the positions are relative to, e.g. `DP-2`
+        #[arg(short = 'o', long = "output")]
+        output: Option<String>,
+
+        /// Button to hold: left, right, middle, side, extra, forward, back, task or a raw code
+        #[arg(short = 'b', long = "button", default_value = "left", value_parser = parse_button)]
+        button: u32,
+
+        /// Duration of the move like `300ms` or `1.5s`
+        #[arg(long = "duration", default_value = "300ms", value_parser = parse_duration)]
+        duration: Duration,
+
+        /// Number of motion events of the move, one per 8ms by default
+        #[arg(long = "steps")]
+        steps: Option<u32>,
+
+        /// Easing of the move: linear, ease-in, ease-out or ease-in-out
+        #[arg(long = "easing", default_value = "linear")]
+        easing: Easing,
     },
     Type {
         /// Delay N ms between key down/up
@@ -70,7 +182,8 @@ pub enum Commands {
         #[arg(short = 'f', long = "file")]
         file: Option<String>,
 
-        /// Escape enable (1) or disable (0)
+        /// Escape enable (1) or disable (0), enabled by default for strings and disabled for files.
+        /// Expands `\n`, `\t`, `\\` and key chords like `\{Return}` or `\{ctrl+a}`
         #[arg(short = 'e', long = "escape")]
         escape: Option<u8>,
 
@@ -79,31 +192,223 @@ pub enum Commands {
         strings: Vec<String>,
     },
     Key {
-        #[arg(value_delimiter = ' ', num_args = 1.., value_parser = parse_keypress)]
-        key_presses: Vec<KeyPress>,
+        /// Keys as `<key>[+<key>...][:<state>]`, where key is an evdev code (`29`), keysym name (`Return`, `ctrl`)
+        /// or evdev name (`KEY_LEFTCTRL`) and state is `1`/`down` or `0`/`up`; without a state the keys are tapped.
+        /// Chords like `ctrl+alt+Delete` are pressed in order and released in reverse
+        #[arg(value_delimiter = ' ', num_args = 1.., value_parser = parse_key_input)]
+        key_inputs: Vec<KeyInput>,
 
         #[arg(short = 'd', long = "key-delay")]
         key_delay: Option<u64>,
     },
     Stdin,
+    /// Print where the pointer is, in the layout and within the output it's on
+    PointerPosition {
+        /// Print shell variable assignments, to be used with `eval`
+        #[arg(long = "shell")]
+        shell: bool,
+    },
+    /// Run commands from a script, one per line: `type`, `key`, `click`, `mousemove`, `scroll` and `drag` with their usual arguments,
+    /// `sleep <ms>`, and comments starting with `#`. Words are quoted like in a shell
+    Run {
+        /// Script file (or "-" for stdin)
+        file: String,
+
+        /// Play sleeps and delays N times as fast
+        #[arg(short = 's', long = "speed", default_value_t = 1.0, value_parser = parse_speed)]
+        speed: f64,
+
+        /// Skip sleeps and delays, running as fast as possible
+        #[arg(short = 'n', long = "no-delays")]
+        no_delays: bool,
+
+        /// Vary each sleep and delay randomly by up to N ms
+        #[arg(short = 'j', long = "jitter", default_value_t = 0)]
+        jitter: u64,
+
+        /// Run the script N times, 0 repeats it until interrupted
+        #[arg(short = 'l', long = "loop", default_value_t = 1)]
+        loops: u32,
+    },
+    /// Record keyboard and pointer input into a script for `run` until Ctrl+C.
+    /// Input is read from `/dev/input`, which usually requires being in the `input` group
+    Record {
+        /// Script file to write, stdout by default
+        #[arg(short = 'o', long = "output")]
+        output: Option<String>,
+    },
+    /// Keep one session with the compositor open, other commands are sent to it while it runs
+    Daemon,
+    /// Forget the portal permission persisted by previous runs, so the next run asks for it again
+    #[cfg(feature = "portals")]
+    ForgetPermission,
 }
 
-fn parse_keypress(s: &str) -> Result<KeyPress, String> {
-    let mut parts = s.split(':');
+impl Commands {
+    /// Whether the command can be a line of a script
+    pub fn scriptable(&self) -> bool {
+        match self {
+            Self::Click { .. }
+            | Self::Mousemove { .. }
+            | Self::Scroll { .. }
+            | Self::Drag { .. }
+            | Self::Key { .. } => true,
+            #[cfg(feature = "portals")]
+            Self::Touch { .. } => true,
+            Self::Type { file, .. } => file.as_deref() != Some("-"),
+            Self::Stdin
+            | Self::PointerPosition { .. }
+            | Self::Run { .. }
+            | Self::Record { .. }
+            | Self::Daemon => false,
+            #[cfg(feature = "portals")]
+            Self::ForgetPermission => false,
+        }
+    }
+
+    /// Whether the command can be run by a daemon, rather than needing this process' terminal or stdin
+    pub fn forwardable(&self) -> bool {
+        match self {
+            // Endless loops would keep the daemon busy, where Ctrl+C can't stop them
+            Self::Run { file, loops, .. } => file != "-" && *loops != 0,
+            cmd => cmd.scriptable(),
+        }
+    }
+}
+
+#[cfg(feature = "portals")]
+#[derive(Subcommand, Debug, Clone)]
+pub enum TouchGesture {
+    /// Touch a position briefly
+    Tap {
+        /// Position as `X,Y`
+        #[arg(value_parser = parse_position)]
+        position: (Coordinate, Coordinate),
+
+        /// How long the finger stays down, like `50ms`
+        #[arg(long = "hold", default_value = "50ms", value_parser = parse_duration)]
+        hold: Duration,
+    },
+    /// Slide one finger from a position to another
+    Swipe {
+        /// Position to touch first as `X,Y`
+        #[arg(long = "from", value_parser = parse_position)]
+        from: (Coordinate, Coordinate),
+
+        /// Position to lift at as `X,Y`
+        #[arg(long = "to", value_parser = parse_position)]
+        to: (Coordinate, Coordinate),
+
+        #[command(flatten)]
+        motion: TouchMotion,
+    },
+    /// Move two fingers apart or together around a center, zooming in or out
+    Pinch {
+        /// Position halfway between the fingers as `X,Y`
+        #[arg(long = "center", value_parser = parse_position)]
+        center: (Coordinate, Coordinate),
+
+        /// Distance between the fingers in pixels when they touch
+        #[arg(long = "from")]
+        from: f64,
+
+        /// Distance between the fingers in pixels when they're lifted
+        #[arg(long = "to")]
+        to: f64,
+
+        #[command(flatten)]
+        motion: TouchMotion,
+    },
+}
+
+#[cfg(feature = "portals")]
+#[derive(Args, Debug, Clone)]
+pub struct TouchMotion {
+    /// Duration of the gesture like `300ms` or `1.5s`
+    #[arg(long = "duration", default_value = "300ms", value_parser = parse_duration)]
+    pub duration: Duration,
+
+    /// Number of motion events, one per 8ms by default
+    #[arg(long = "steps")]
+    pub steps: Option<u32>,
 
-    let keycode_str = parts.next().ok_or("Missing keycode")?;
-    let keycode: u32 = keycode_str
-        .parse()
-        .map_err(|_: ParseIntError| format!("Invalid keycode '{keycode_str}'"))?;
+    /// Easing of the gesture: linear, ease-in, ease-out or ease-in-out
+    #[arg(long = "easing", default_value = "linear")]
+    pub easing: Easing,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum KeyAction {
+    Press,
+    Release,
+    Tap,
+}
+
+#[derive(Clone, Debug)]
+pub struct KeyInput {
+    pub keys: Vec<Key>,
+    pub action: KeyAction,
+}
+
+/// Parses `300ms`, `1.5s` or a plain number of ms
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = if let Some(ms) = s.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        (secs, 1.0)
+    } else {
+        (s, 0.001)
+    };
+
+    number
+        .parse::<f64>()
+        .ok()
+        .and_then(|number| Duration::try_from_secs_f64(number * unit).ok())
+        .ok_or_else(|| format!("Invalid duration '{s}'"))
+}
+
+fn parse_point(s: &str) -> Result<(f64, f64), String> {
+    s.split_once(',')
+        .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+        .ok_or_else(|| format!("Expected a point like `100,-50`, got '{s}'"))
+}
+
+fn parse_position(s: &str) -> Result<(Coordinate, Coordinate), String> {
+    s.split_once(',')
+        .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+        .ok_or_else(|| format!("Expected a position like `100,200` or `50%,50%`, got '{s}'"))
+}
+
+fn parse_button(s: &str) -> Result<u32, String> {
+    button_code(s).ok_or_else(|| format!("Unknown button '{s}'"))
+}
+
+fn parse_speed(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err(format!("Speed must be a positive number, got '{s}'")),
+    }
+}
 
-    let pressed_str = parts.next().ok_or("Missing pressed state")?;
-    let pressed = match pressed_str {
-        "0" => xkb::KeyDirection::Up,
-        "1" => xkb::KeyDirection::Down,
-        _ => {
-            return Err(format!("Pressed state must be 0 or 1, got '{pressed_str}'",));
+fn parse_key_input(s: &str) -> Result<KeyInput, String> {
+    let (key_str, action) = match s.rsplit_once(':') {
+        Some((key_str, state_str)) => {
+            let action = match state_str {
+                "1" | "down" => KeyAction::Press,
+                "0" | "up" => KeyAction::Release,
+                _ => {
+                    return Err(format!(
+                        "Pressed state must be 0, 1, down or up, got '{state_str}'"
+                    ));
+                }
+            };
+            (key_str, action)
         }
+        None => (s, KeyAction::Tap),
     };
 
-    Ok(KeyPress { keycode, pressed })
+    Ok(KeyInput {
+        keys: parse_chord(key_str)?,
+        action,
+    })
 }
@@ -0,0 +1,70 @@
+use std::{fmt, str::FromStr};
+
+/// Position along an axis, in pixels or as a percentage like `50%` of the space it's in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coordinate {
+    Pixels(f64),
+    Percent(f64),
+}
+
+impl Coordinate {
+    /// Offset in pixels, `None` for percentages which only make sense within a known size
+    #[must_use]
+    pub const fn pixels(self) -> Option<f64> {
+        match self {
+            Self::Pixels(pixels) => Some(pixels),
+            Self::Percent(_) => None,
+        }
+    }
+
+    /// Absolute pixel on an axis `size` pixels long, `None` unless it's a whole non-negative
+    /// number of pixels or a percentage between 0 and 100
+    #[must_use]
+    pub fn resolve(self, size: u32) -> Option<u32> {
+        match self {
+            Self::Pixels(pixels)
+                if pixels.fract() == 0.0 && (0.0..=f64::from(u32::MAX)).contains(&pixels) =>
+            {
+                Some(pixels as u32)
+            }
+            // 100% is the last pixel, not the one past the edge
+            Self::Percent(percent) if (0.0..=100.0).contains(&percent) => {
+                let pixel = (f64::from(size) * percent / 100.0).round() as u32;
+                Some(pixel.min(size.saturating_sub(1)))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Coordinate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, percent) = match s.strip_suffix('%') {
+            Some(number) => (number, true),
+            None => (s, false),
+        };
+
+        let value = number
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| format!("Invalid position '{s}'"))?;
+
+        Ok(if percent {
+            Self::Percent(value)
+        } else {
+            Self::Pixels(value)
+        })
+    }
+}
+
+impl fmt::Display for Coordinate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pixels(pixels) => write!(f, "{pixels}"),
+            Self::Percent(percent) => write!(f, "{percent}%"),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{
+    cli::{Cli, Commands},
+    devices::Devices,
+};
+use anyhow::Context;
+use clap::{CommandFactory, FromArgMatches, parser::ValueSource};
+use libwhydotool::Player;
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    io::{Read, Write},
+    iter,
+    net::Shutdown,
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+const REPLY_OK: u8 = 0;
+const REPLY_ERR: u8 = 1;
+// Clients send their request right away, one that doesn't would hold up everyone else
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Socket of the daemon serving the current Wayland display
+fn socket_path() -> Option<PathBuf> {
+    let runtime_dir = PathBuf::from(env::var_os("XDG_RUNTIME_DIR")?);
+    let display = env::var_os("WAYLAND_DISPLAY").unwrap_or_else(|| "wayland-0".into());
+    // WAYLAND_DISPLAY may also be an absolute path
+    let display = Path::new(&display)
+        .file_name()?
+        .to_string_lossy()
+        .into_owned();
+
+    Some(runtime_dir.join(format!("whydotool-{display}.sock")))
+}
+
+/// Serves commands sent by other whydotool processes, one at a time
+pub fn run(devices: &mut Devices) -> anyhow::Result<()> {
+    let path = socket_path().context("XDG_RUNTIME_DIR is not set")?;
+    if UnixStream::connect(&path).is_ok() {
+        anyhow::bail!("Daemon is already running on {}", path.display());
+    }
+
+    // Left behind by a daemon that didn't exit cleanly
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+
+    let socket = path.clone();
+    ctrlc::set_handler(move || {
+        crate::drag::interrupt();
+        let _ = fs::remove_file(&socket);
+        process::exit(0);
+    })?;
+
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+
+        let reply = match handle(devices, &mut stream) {
+            Ok(()) => vec![REPLY_OK],
+            Err(e) => iter::once(REPLY_ERR)
+                .chain(format!("{e:#}").into_bytes())
+                .collect(),
+        };
+
+        let _ = stream.write_all(&reply);
+    }
+
+    Ok(())
+}
+
+/// Runs a request made of the client's working directory followed by its arguments, each terminated by NUL
+fn handle(devices: &mut Devices, stream: &mut UnixStream) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request = Vec::new();
+    stream
+        .read_to_end(&mut request)
+        .context("Client didn't finish its request")?;
+
+    let mut fields = request
+        .strip_suffix(b"\0")
+        .unwrap_or(&request)
+        .split(|b| *b == 0)
+        .map(OsStr::from_bytes);
+
+    let cwd = fields.next().map(PathBuf::from).unwrap_or_default();
+    let matches =
+        Cli::command().try_get_matches_from(iter::once(OsStr::new("whydotool")).chain(fields))?;
+    // Devices were created when the daemon started, flags for creating them can't apply anymore
+    if matches
+        .ids()
+        .any(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+    {
+        anyhow::bail!(
+            "Global flags can't be applied by the daemon, which already created its devices"
+        );
+    }
+    let cli = Cli::from_arg_matches(&matches)?;
+
+    // Events that arrived while idle, like outputs or the keymap changing, apply to this request
+    devices.whydotool().roundtrip()?;
+
+    let mut cmd = cli.cmd;
+    if !cmd.forwardable() {
+        anyhow::bail!("Command can't be run by the daemon");
+    }
+
+    // Files are opened by the daemon, relative to the client's directory
+    if let Commands::Type {
+        file: Some(file), ..
+    }
+    | Commands::Run { file, .. } = &mut cmd
+    {
+        *file = cwd.join(&*file).to_string_lossy().into_owned();
+    }
+
+    crate::execute(devices, cmd, &Player::default())
+}
+
+/// Sends a command to the daemon if one is running, returning false if there's none
+pub fn forward(args: impl Iterator<Item = OsString>) -> anyhow::Result<bool> {
+    let Some(mut stream) = socket_path().and_then(|path| UnixStream::connect(path).ok()) else {
+        return Ok(false);
+    };
+
+    let mut request = env::current_dir()?.into_os_string().into_vec();
+    request.push(0);
+    for arg in args {
+        request.extend(arg.as_bytes());
+        request.push(0);
+    }
+
+    stream.write_all(&request)?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply)?;
+
+    match reply.split_first() {
+        Some((&REPLY_OK, _)) => Ok(true),
+        Some((_, message)) => anyhow::bail!("{}", String::from_utf8_lossy(message)),
+        None => anyhow::bail!("Daemon closed the connection"),
+    }
+}
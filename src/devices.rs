@@ -0,0 +1,88 @@
+#[cfg(feature = "portals")]
+use libwhydotool::VirtualTouch;
+use libwhydotool::{Result, VirtualKeyboard, VirtualPointer, Whydotool};
+
+/// Virtual devices created on first use and kept for the following commands
+pub struct Devices {
+    whydotool: Whydotool,
+    keyboard: Option<Box<dyn VirtualKeyboard>>,
+    /// Keymap the keyboard was created with
+    keymap_serial: u64,
+    pointer: Option<Box<dyn VirtualPointer>>,
+    #[cfg(feature = "portals")]
+    touch: Option<Box<dyn VirtualTouch>>,
+    #[cfg(feature = "portals")]
+    share_all_outputs: bool,
+}
+
+impl Devices {
+    pub const fn new(whydotool: Whydotool) -> Self {
+        Self {
+            whydotool,
+            keyboard: None,
+            keymap_serial: 0,
+            pointer: None,
+            #[cfg(feature = "portals")]
+            touch: None,
+            #[cfg(feature = "portals")]
+            share_all_outputs: false,
+        }
+    }
+
+    #[cfg(feature = "portals")]
+    pub fn force_portal(&mut self, force_portal: bool) {
+        self.whydotool.force_portal(force_portal);
+    }
+
+    /// Prepares for devices placed on `output`, which portal sessions only find when they share every monitor.
+    /// Devices of sessions that don't are created again
+    pub fn use_output(&mut self, output: Option<&str>) {
+        #[cfg(feature = "portals")]
+        if output.is_some() && !self.share_all_outputs {
+            self.share_all_outputs = true;
+            self.whydotool.share_all_outputs(true);
+            self.pointer = None;
+            self.touch = None;
+        }
+        #[cfg(not(feature = "portals"))]
+        let _ = output;
+    }
+
+    pub const fn whydotool(&mut self) -> &mut Whydotool {
+        &mut self.whydotool
+    }
+
+    /// Keyboard, created again once the compositor's keymap changed
+    pub fn keyboard(&mut self) -> Result<(&mut Whydotool, &mut dyn VirtualKeyboard)> {
+        let keymap_serial = self.whydotool.keymap_serial();
+        let keyboard = match self.keyboard.take() {
+            Some(keyboard) if keymap_serial == self.keymap_serial => keyboard,
+            _ => {
+                let keyboard = self.whydotool.virtual_keyboard()?;
+                self.keymap_serial = keymap_serial;
+                keyboard
+            }
+        };
+
+        Ok((&mut self.whydotool, self.keyboard.insert(keyboard).as_mut()))
+    }
+
+    pub fn pointer(&mut self) -> Result<(&mut Whydotool, &dyn VirtualPointer)> {
+        let pointer = match self.pointer.take() {
+            Some(pointer) => pointer,
+            None => self.whydotool.virtual_pointer()?,
+        };
+
+        Ok((&mut self.whydotool, &**self.pointer.insert(pointer)))
+    }
+
+    #[cfg(feature = "portals")]
+    pub fn touch(&mut self) -> Result<(&mut Whydotool, &dyn VirtualTouch)> {
+        let touch = match self.touch.take() {
+            Some(touch) => touch,
+            None => self.whydotool.virtual_touch()?,
+        };
+
+        Ok((&mut self.whydotool, &**self.touch.insert(touch)))
+    }
+}
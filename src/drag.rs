@@ -0,0 +1,76 @@
+use libwhydotool::{SmoothMotion, VirtualPointer, Whydotool};
+use std::{
+    process,
+    sync::{
+        Once,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+use wayland_client::protocol::wl_pointer::ButtonState;
+
+static DRAGGING: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static CTRLC_HANDLER: Once = Once::new();
+
+/// Stops a running drag, returning once its button is released
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    while DRAGGING.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Holds `button` while moving from `from` to `to`, releasing it even if the move fails or is interrupted
+pub fn drag(
+    whydotool: &mut Whydotool,
+    virtual_pointer: &dyn VirtualPointer,
+    output: Option<&str>,
+    from: (u32, u32),
+    to: (u32, u32),
+    button: u32,
+    smooth_motion: &SmoothMotion,
+) -> anyhow::Result<()> {
+    // The daemon has a handler of its own, which interrupts drags itself
+    CTRLC_HANDLER.call_once(|| {
+        let _ = ctrlc::set_handler(|| {
+            interrupt();
+            process::exit(130);
+        });
+    });
+
+    match output {
+        Some(output) => virtual_pointer.motion_absolute_output(output, from.0, from.1)?,
+        None => virtual_pointer.motion_absolute(from.0, from.1)?,
+    }
+    whydotool.roundtrip()?;
+
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    DRAGGING.store(true, Ordering::SeqCst);
+
+    let mut held = || -> libwhydotool::Result<()> {
+        virtual_pointer.button(button, ButtonState::Pressed)?;
+        whydotool.roundtrip()?;
+
+        let from = (f64::from(from.0), f64::from(from.1));
+        smooth_motion
+            .clone()
+            .interruptible(&INTERRUPTED)
+            .motion_absolute(virtual_pointer, output, from, to, || {
+                whydotool.roundtrip()?;
+                Ok(())
+            })
+    };
+    let held = held();
+
+    let released = virtual_pointer
+        .button(button, ButtonState::Released)
+        .and_then(|()| whydotool.roundtrip().map(|_| ()));
+    DRAGGING.store(false, Ordering::SeqCst);
+
+    held?;
+    released?;
+
+    Ok(())
+}
@@ -0,0 +1,473 @@
+//! Client side of the EI protocol, emulating input through a socket from the portal's `ConnectToEIS`
+//! instead of a D-Bus call per event
+
+use crate::{Error, Result};
+use nix::time::{ClockId, clock_gettime};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, Read, Write},
+    os::{fd::OwnedFd, unix::net::UnixStream},
+    time::{Duration, Instant},
+};
+
+// Version 1 of every interface, later ones only add what isn't used here
+const VERSION: u32 = 1;
+const CONTEXT_SENDER: u32 = 2;
+const TIMEOUT: Duration = Duration::from_secs(1);
+// Object id, length and opcode
+const HEADER_SIZE: usize = 16;
+
+pub const POINTER: &str = "ei_pointer";
+pub const POINTER_ABSOLUTE: &str = "ei_pointer_absolute";
+pub const SCROLL: &str = "ei_scroll";
+pub const BUTTON: &str = "ei_button";
+pub const KEYBOARD: &str = "ei_keyboard";
+pub const TOUCHSCREEN: &str = "ei_touchscreen";
+
+/// Interfaces announced in the handshake
+const INTERFACES: &[&str] = &[
+    "ei_connection",
+    "ei_callback",
+    "ei_pingpong",
+    "ei_seat",
+    "ei_device",
+    POINTER,
+    POINTER_ABSOLUTE,
+    SCROLL,
+    BUTTON,
+    KEYBOARD,
+    TOUCHSCREEN,
+];
+
+/// Argument of a request
+pub enum Arg<'a> {
+    Uint(u32),
+    Int(i32),
+    Uint64(u64),
+    Float(f32),
+    Str(&'a str),
+}
+
+enum Object {
+    Handshake,
+    Connection,
+    Callback,
+    Seat,
+    Device,
+    /// Interface of a device, like `ei_pointer`
+    Interface(String),
+}
+
+#[derive(Default)]
+struct Seat {
+    /// Masks to bind interfaces with
+    capabilities: HashMap<String, u64>,
+    done: bool,
+}
+
+struct Device {
+    id: u64,
+    interfaces: HashMap<String, u64>,
+    resumed: bool,
+    emulating: bool,
+}
+
+#[derive(Default)]
+struct State {
+    objects: HashMap<u64, Object>,
+    next_id: u64,
+    /// Last serial sent by the server
+    serial: u32,
+    sequence: u32,
+    handshake_version: Option<u32>,
+    seats: HashMap<u64, Seat>,
+    devices: Vec<Device>,
+    callbacks_done: Vec<u64>,
+    disconnected: Option<String>,
+    incoming: Vec<u8>,
+}
+
+impl State {
+    fn new_id(&mut self, object: Object) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.objects.insert(id, object);
+        id
+    }
+
+    /// Resumed device with `interface`
+    fn device(&mut self, interface: &str) -> Option<&mut Device> {
+        self.devices
+            .iter_mut()
+            .find(|device| device.resumed && device.interfaces.contains_key(interface))
+    }
+}
+
+/// EI connection of a sender context, with devices of the interfaces it was bound to
+pub struct Eis {
+    socket: UnixStream,
+    state: RefCell<State>,
+}
+
+impl Eis {
+    /// Connects over `socket` from the portal's `ConnectToEIS`, binding seats to devices with `interfaces`
+    ///
+    /// # Errors
+    ///
+    /// The handshake failed, or the EIS server offers none of `interfaces`
+    pub fn connect(socket: impl Into<OwnedFd>, interfaces: &[&str]) -> Result<Self> {
+        let eis = Self {
+            socket: UnixStream::from(socket.into()),
+            state: RefCell::new(State {
+                objects: HashMap::from([(0, Object::Handshake)]),
+                next_id: 1,
+                ..State::default()
+            }),
+        };
+
+        eis.wait(|state| state.handshake_version.is_some())?;
+        eis.send(0, 0, &[Arg::Uint(VERSION)])?;
+        eis.send(0, 2, &[Arg::Uint(CONTEXT_SENDER)])?;
+        eis.send(0, 3, &[Arg::Str("whydotool")])?;
+        for interface in INTERFACES {
+            eis.send(0, 4, &[Arg::Str(interface), Arg::Uint(VERSION)])?;
+        }
+        eis.send(0, 1, &[])?;
+
+        eis.wait(|state| !state.seats.is_empty() && state.seats.values().all(|seat| seat.done))?;
+
+        let binds: Vec<_> = eis
+            .state
+            .borrow()
+            .seats
+            .iter()
+            .map(|(id, seat)| {
+                let capabilities = interfaces
+                    .iter()
+                    .filter_map(|interface| seat.capabilities.get(*interface))
+                    .fold(0, |mask, capability| mask | capability);
+                (*id, capabilities)
+            })
+            .filter(|(_, capabilities)| *capabilities != 0)
+            .collect();
+        if binds.is_empty() {
+            return Err(Error::Eis("No seat has the devices needed".into()));
+        }
+        for (seat, capabilities) in binds {
+            eis.send(seat, 1, &[Arg::Uint64(capabilities)])?;
+        }
+
+        // Devices are added after binding, once for each interface is enough to start with
+        eis.wait(|state| {
+            interfaces.iter().any(|interface| {
+                state
+                    .devices
+                    .iter()
+                    .any(|device| device.resumed && device.interfaces.contains_key(*interface))
+            })
+        })?;
+
+        Ok(eis)
+    }
+
+    /// Sends a request on `interface` of a device within a frame of its own
+    ///
+    /// # Errors
+    ///
+    /// No device has `interface`, or the connection failed
+    pub fn request(&self, interface: &str, opcode: u32, args: &[Arg]) -> Result<()> {
+        self.receive(Duration::ZERO)?;
+        // Devices are paused while the compositor doesn't accept input, like when the screen is locked
+        self.wait(|state| state.device(interface).is_some())
+            .map_err(|_| Error::Eis(format!("No device for {interface}").into()))?;
+
+        let (device, object, start) = {
+            let mut state = self.state.borrow_mut();
+            let device = state
+                .device(interface)
+                .ok_or_else(|| Error::Eis(format!("No device for {interface}").into()))?;
+            let start = !device.emulating;
+            device.emulating = true;
+            let ids = (device.id, device.interfaces[interface]);
+            if start {
+                state.sequence += 1;
+            }
+            (
+                ids.0,
+                ids.1,
+                start.then_some((state.serial, state.sequence)),
+            )
+        };
+
+        if let Some((serial, sequence)) = start {
+            self.send(device, 1, &[Arg::Uint(serial), Arg::Uint(sequence)])?;
+        }
+        self.send(object, opcode, args)?;
+
+        let serial = self.state.borrow().serial;
+        let timestamp = clock_gettime(ClockId::CLOCK_MONOTONIC).map_err(io::Error::from)?;
+        let micros = timestamp.tv_sec() as u64 * 1_000_000 + timestamp.tv_nsec() as u64 / 1000;
+        self.send(device, 3, &[Arg::Uint(serial), Arg::Uint64(micros)])
+    }
+
+    /// Waits until the server has handled every request sent before
+    ///
+    /// # Errors
+    ///
+    /// The connection failed
+    pub fn sync(&self) -> Result<()> {
+        let callback = self.state.borrow_mut().new_id(Object::Callback);
+        let connection = self
+            .state
+            .borrow()
+            .objects
+            .iter()
+            .find_map(|(id, object)| matches!(object, Object::Connection).then_some(*id))
+            .ok_or_else(|| Error::Eis("Not connected".into()))?;
+
+        self.send(connection, 0, &[Arg::Uint64(callback), Arg::Uint(VERSION)])?;
+        self.wait(|state| state.callbacks_done.contains(&callback))
+    }
+
+    fn send(&self, object: u64, opcode: u32, args: &[Arg]) -> Result<()> {
+        let mut body = Vec::new();
+        for arg in args {
+            match arg {
+                Arg::Uint(value) => body.extend(value.to_ne_bytes()),
+                Arg::Int(value) => body.extend(value.to_ne_bytes()),
+                Arg::Uint64(value) => body.extend(value.to_ne_bytes()),
+                Arg::Float(value) => body.extend(value.to_ne_bytes()),
+                Arg::Str(value) => {
+                    // Length counts the terminating null, the string is padded to 4 bytes
+                    let length = value.len() + 1;
+                    body.extend((length as u32).to_ne_bytes());
+                    body.extend(value.as_bytes());
+                    body.resize(body.len() + 1 + (4 - length % 4) % 4, 0);
+                }
+            }
+        }
+
+        let mut message = Vec::with_capacity(HEADER_SIZE + body.len());
+        message.extend(object.to_ne_bytes());
+        message.extend(((HEADER_SIZE + body.len()) as u32).to_ne_bytes());
+        message.extend(opcode.to_ne_bytes());
+        message.extend(body);
+
+        (&self.socket).write_all(&message)?;
+        Ok(())
+    }
+
+    /// Receives events until `condition` holds, for up to a second
+    fn wait(&self, condition: impl Fn(&mut State) -> bool) -> Result<()> {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let mut state = self.state.borrow_mut();
+            if condition(&mut state) {
+                return Ok(());
+            }
+            if let Some(reason) = &state.disconnected {
+                return Err(Error::Eis(reason.as_str().into()));
+            }
+            drop(state);
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::Eis("Timed out waiting for the EIS server".into()));
+            }
+            self.receive(remaining)?;
+        }
+    }
+
+    /// Handles the events that arrive within `timeout`, only those already there if it's zero
+    fn receive(&self, timeout: Duration) -> Result<()> {
+        if timeout.is_zero() {
+            self.socket.set_nonblocking(true)?;
+        } else {
+            self.socket.set_read_timeout(Some(timeout))?;
+        }
+
+        // File descriptors, like the keymap's, are left to be closed with the message
+        let mut buffer = [0; 4096];
+        let read = (&self.socket).read(&mut buffer);
+        self.socket.set_nonblocking(false)?;
+
+        let mut state = self.state.borrow_mut();
+        match read {
+            Ok(0) => state.disconnected = Some("EIS server closed the connection".to_string()),
+            Ok(read) => state.incoming.extend(&buffer[..read]),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        while state.incoming.len() >= HEADER_SIZE {
+            let object = u64::from_ne_bytes(state.incoming[..8].try_into().unwrap_or_default());
+            let length =
+                u32::from_ne_bytes(state.incoming[8..12].try_into().unwrap_or_default()) as usize;
+            let opcode = u32::from_ne_bytes(state.incoming[12..16].try_into().unwrap_or_default());
+            if length < HEADER_SIZE {
+                return Err(Error::Eis("Malformed message".into()));
+            }
+            if state.incoming.len() < length {
+                break;
+            }
+
+            let message: Vec<_> = state.incoming.drain(..length).collect();
+            self.handle(
+                &mut state,
+                object,
+                opcode,
+                &mut Reader(&message[HEADER_SIZE..]),
+            )
+            .ok_or_else(|| Error::Eis("Malformed message".into()))??;
+        }
+
+        Ok(())
+    }
+
+    /// Handles an event, `None` if its arguments are malformed
+    fn handle(
+        &self,
+        state: &mut State,
+        object: u64,
+        opcode: u32,
+        args: &mut Reader,
+    ) -> Option<Result<()>> {
+        match (state.objects.get(&object)?, opcode) {
+            (Object::Handshake, 0) => state.handshake_version = Some(args.uint()?),
+            (Object::Handshake, 2) => {
+                state.serial = args.uint()?;
+                state.objects.insert(args.uint64()?, Object::Connection);
+            }
+            (Object::Connection, 0) => {
+                state.serial = args.uint()?;
+                let _reason = args.uint()?;
+                let explanation = args.string()?;
+                state.disconnected = Some(format!("EIS server disconnected: {explanation}"));
+            }
+            (Object::Connection, 1) => {
+                let seat = args.uint64()?;
+                state.objects.insert(seat, Object::Seat);
+                state.seats.insert(seat, Seat::default());
+            }
+            // Pings are answered right away
+            (Object::Connection, 3) => {
+                return Some(self.send(args.uint64()?, 0, &[Arg::Uint64(0)]));
+            }
+            (Object::Callback, 0) => state.callbacks_done.push(object),
+            (Object::Seat, 0) => {
+                state.serial = args.uint()?;
+                state.seats.remove(&object);
+            }
+            (Object::Seat, 2) => {
+                let mask = args.uint64()?;
+                let interface = args.string()?;
+                state
+                    .seats
+                    .get_mut(&object)?
+                    .capabilities
+                    .insert(interface, mask);
+            }
+            (Object::Seat, 3) => state.seats.get_mut(&object)?.done = true,
+            (Object::Seat, 4) => {
+                let device = args.uint64()?;
+                state.objects.insert(device, Object::Device);
+                state.devices.push(Device {
+                    id: device,
+                    interfaces: HashMap::new(),
+                    resumed: false,
+                    emulating: false,
+                });
+            }
+            (Object::Device, 0) => {
+                state.serial = args.uint()?;
+                state.devices.retain(|device| device.id != object);
+            }
+            (Object::Device, 5) => {
+                let id = args.uint64()?;
+                let interface = args.string()?;
+                state
+                    .objects
+                    .insert(id, Object::Interface(interface.clone()));
+                let device = state
+                    .devices
+                    .iter_mut()
+                    .find(|device| device.id == object)?;
+                device.interfaces.insert(interface, id);
+            }
+            (Object::Device, 7 | 8) => {
+                state.serial = args.uint()?;
+                let device = state
+                    .devices
+                    .iter_mut()
+                    .find(|device| device.id == object)?;
+                // Paused devices stop emulating, it's started again once they resume
+                device.resumed = opcode == 7;
+                device.emulating = false;
+            }
+            // Modifiers carry a serial
+            (Object::Interface(interface), 2) if interface == KEYBOARD => {
+                state.serial = args.uint()?;
+            }
+            _ => {}
+        }
+
+        Some(Ok(()))
+    }
+}
+
+impl Drop for Eis {
+    fn drop(&mut self) {
+        let emulating: Vec<_> = self
+            .state
+            .borrow()
+            .devices
+            .iter()
+            .filter(|device| device.emulating)
+            .map(|device| device.id)
+            .collect();
+        let serial = self.state.borrow().serial;
+        for device in emulating {
+            let _ = self.send(device, 2, &[Arg::Uint(serial)]);
+        }
+
+        // The portal session may close first otherwise, before the server got to the last events
+        let _ = self.sync();
+    }
+}
+
+/// Arguments of an event
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.0.split_first_chunk::<N>()?;
+        self.0 = rest;
+        Some(*bytes)
+    }
+
+    fn uint(&mut self) -> Option<u32> {
+        self.take().map(u32::from_ne_bytes)
+    }
+
+    fn uint64(&mut self) -> Option<u64> {
+        self.take().map(u64::from_ne_bytes)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let length = self.uint()? as usize;
+        let padded = length.next_multiple_of(4);
+        if self.0.len() < padded {
+            return None;
+        }
+
+        let (bytes, rest) = self.0.split_at(padded);
+        self.0 = rest;
+        let bytes = &bytes[..length.saturating_sub(1)];
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
@@ -0,0 +1,85 @@
+use crate::Key;
+use std::io;
+use wayland_client::{ConnectError, DispatchError, globals::GlobalError};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("No seat available")]
+    NoSeat,
+
+    #[error(
+        "No keymap information available. Make sure a keyboard is connected and the keymap event has been received."
+    )]
+    NoKeymap,
+
+    #[error("Failed to compile keymap")]
+    InvalidKeymap,
+
+    #[error("Unknown key '{0}'")]
+    UnknownKey(Key),
+
+    #[error("Character '{0}' can't be typed with this keyboard")]
+    UntypableChar(char),
+
+    #[error("No output named '{0}'")]
+    UnknownOutput(Box<str>),
+
+    #[error("Pointer position is unknown, it didn't enter any output")]
+    UnknownPointerPosition,
+
+    #[error("Size of the screen is unknown")]
+    UnknownSize,
+
+    #[error("Compositor does not support {0} protocol, compile whydotool with `portals` feature")]
+    ProtocolUnsupported(&'static str),
+
+    #[error("Failed to connect to Wayland: {0}")]
+    Connect(#[from] ConnectError),
+
+    #[error("Wayland dispatch failed: {0}")]
+    Dispatch(#[from] DispatchError),
+
+    #[error("Failed to retrieve Wayland globals: {0}")]
+    Globals(#[from] GlobalError),
+
+    #[cfg(feature = "portals")]
+    #[error("Portal request was rejected")]
+    PortalRejected,
+
+    #[cfg(feature = "portals")]
+    #[error("Timed out waiting for portal response")]
+    PortalTimeout,
+
+    #[cfg(feature = "portals")]
+    #[error("Portal returned an invalid response")]
+    PortalResponse,
+
+    #[cfg(feature = "portals")]
+    #[error("Portal does not offer a {0} device")]
+    PortalDeviceUnsupported(&'static str),
+
+    #[cfg(feature = "portals")]
+    #[error("D-Bus: {0}")]
+    DBus(#[from] zbus::Error),
+
+    #[cfg(feature = "portals")]
+    #[error("No PipeWire stream available")]
+    NoStream,
+
+    #[cfg(feature = "portals")]
+    #[error("PipeWire streams didn't start in time")]
+    StreamTimeout,
+
+    #[cfg(feature = "portals")]
+    #[error("EIS: {0}")]
+    Eis(Box<str>),
+
+    #[cfg(feature = "portals")]
+    #[error("PipeWire: {0}")]
+    PipeWire(#[from] pipewire::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
@@ -0,0 +1,202 @@
+use crate::{KeyPress, evdev};
+use std::{fmt, str::FromStr};
+use xkbcommon::xkb::{self, KeyDirection, Keycode, Keysym};
+
+/// Short names accepted on top of the regular keysym names
+const ALIASES: &[(&str, &str)] = &[
+    ("ctrl", "Control_L"),
+    ("control", "Control_L"),
+    ("shift", "Shift_L"),
+    ("alt", "Alt_L"),
+    ("altgr", "ISO_Level3_Shift"),
+    ("super", "Super_L"),
+    ("win", "Super_L"),
+    ("logo", "Super_L"),
+    ("meta", "Meta_L"),
+    ("enter", "Return"),
+    ("esc", "Escape"),
+    ("del", "Delete"),
+];
+
+/// A key as written by the user, resolved against the virtual keyboard's keymap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Key {
+    /// Raw evdev keycode, e.g. `56`
+    Code(u32),
+    /// Keysym name like `Return` or `ctrl`, or evdev name like `KEY_LEFTCTRL`
+    Name(Box<str>),
+}
+
+impl Key {
+    /// Key for an evdev keycode, named by its `input-event-codes.h` name when it has one
+    #[must_use]
+    pub fn from_evdev(code: u32) -> Self {
+        evdev::key_name(code).map_or(Self::Code(code), |name| Self::Name(name.into()))
+    }
+
+    pub(crate) fn resolve(&self, keymap: &xkb::Keymap) -> Option<Keycode> {
+        match self {
+            // xkbcommon uses keycodes with an offset of 8
+            Self::Code(code) => code.checked_add(8).map(Keycode::new),
+            Self::Name(name) => keysym_from_name(name)
+                .and_then(|keysym| keycode_from_keysym(keymap, keysym))
+                .or_else(|| {
+                    evdev::key_code(name)
+                        .and_then(|code| code.checked_add(8))
+                        .map(Keycode::new)
+                }),
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err("Missing key".to_string());
+        }
+
+        match s.parse::<u32>() {
+            Ok(code) if code > evdev::KEY_MAX => Err(format!(
+                "Keycode {code} is above the highest one, {}",
+                evdev::KEY_MAX
+            )),
+            Ok(code) => Ok(Self::Code(code)),
+            Err(_) => Ok(Self::Name(s.into())),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Code(code) => write!(f, "{code}"),
+            Self::Name(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Parses keys joined with `+`, e.g. `ctrl+alt+Delete`
+///
+/// # Errors
+///
+/// One of the keys is empty, or a keycode is out of range
+pub fn parse_chord(s: &str) -> Result<Vec<Key>, String> {
+    s.split('+').map(str::parse).collect()
+}
+
+/// Key producing a character, along with the modifiers and layout it has to be typed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CharKey {
+    pub keycode: Keycode,
+    pub mods: xkb::ModMask,
+    pub layout: xkb::LayoutIndex,
+}
+
+/// Builds a sequence of key presses, keeping presses and releases balanced for chords
+#[derive(Debug, Clone, Default)]
+pub struct KeySequence(Vec<KeyPress>);
+
+impl KeySequence {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn press(mut self, keycode: Keycode) -> Self {
+        self.0.push(KeyPress::new(keycode, KeyDirection::Down));
+        self
+    }
+
+    #[must_use]
+    pub fn release(mut self, keycode: Keycode) -> Self {
+        self.0.push(KeyPress::new(keycode, KeyDirection::Up));
+        self
+    }
+
+    #[must_use]
+    pub fn tap(self, keycode: Keycode) -> Self {
+        self.press(keycode).release(keycode)
+    }
+
+    /// Presses `keycodes` in order
+    #[must_use]
+    pub fn press_all(self, keycodes: &[Keycode]) -> Self {
+        keycodes
+            .iter()
+            .fold(self, |sequence, keycode| sequence.press(*keycode))
+    }
+
+    /// Releases `keycodes` in reverse order
+    #[must_use]
+    pub fn release_all(self, keycodes: &[Keycode]) -> Self {
+        keycodes
+            .iter()
+            .rev()
+            .fold(self, |sequence, keycode| sequence.release(*keycode))
+    }
+
+    /// Presses every key of the chord, e.g. `ctrl+alt+Delete`, then releases them in reverse order
+    #[must_use]
+    pub fn chord(self, keycodes: &[Keycode]) -> Self {
+        self.press_all(keycodes).release_all(keycodes)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, KeyPress> {
+        self.0.iter()
+    }
+}
+
+impl IntoIterator for KeySequence {
+    type Item = KeyPress;
+    type IntoIter = std::vec::IntoIter<KeyPress>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+fn keysym_from_name(name: &str) -> Option<Keysym> {
+    let name = ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        .map_or(name, |(_, keysym_name)| keysym_name);
+
+    [xkb::KEYSYM_NO_FLAGS, xkb::KEYSYM_CASE_INSENSITIVE]
+        .into_iter()
+        .map(|flags| xkb::keysym_from_name(name, flags))
+        .find(|keysym| *keysym != Keysym::NoSymbol)
+}
+
+/// Finds the key producing `keysym`, preferring the lowest shift level
+pub(crate) fn keycode_from_keysym(keymap: &xkb::Keymap, keysym: Keysym) -> Option<Keycode> {
+    let keycodes = keymap.min_keycode().raw()..=keymap.max_keycode().raw();
+
+    (0..keymap.num_layouts()).find_map(|layout| {
+        let max_level = keycodes
+            .clone()
+            .map(|keycode| keymap.num_levels_for_key(Keycode::new(keycode), layout))
+            .max()
+            .unwrap_or(0);
+
+        (0..max_level).find_map(|level| {
+            keycodes.clone().map(Keycode::new).find(|keycode| {
+                keymap
+                    .key_get_syms_by_level(*keycode, layout, level)
+                    .contains(&keysym)
+            })
+        })
+    })
+}
@@ -1,28 +1,65 @@
+mod button;
+mod coordinate;
+#[cfg(feature = "portals")]
+mod eis;
+mod error;
+mod evdev;
+mod key;
 mod output;
+mod player;
+mod pointer_position;
 #[cfg(feature = "portals")]
 mod portal;
+mod recorder;
+mod scroll;
+mod smooth_motion;
+mod stream;
+mod text;
 mod virtual_device;
 
+pub use button::{Click, button_code, button_name};
+pub use coordinate::Coordinate;
+#[cfg(feature = "portals")]
+use eis::Eis;
+pub use error::{Error, Result};
+pub use evdev::{EvdevDecoder, KEY_MAX};
+pub use key::{CharKey, Key, KeySequence, parse_chord};
 use output::Outputs;
+pub use player::{Player, Schedule};
+pub use pointer_position::PointerPosition;
 #[cfg(feature = "portals")]
 use portal::remote_desktop::RemoteDesktop;
-use std::fmt;
-use std::sync::{Arc, Mutex};
 #[cfg(feature = "portals")]
-use virtual_device::{keyboard::portal::PortalKeyboard, pointer::portal::PortalPointer};
+pub use portal::restore_tokens::RestoreTokens;
+pub use recorder::{Event, RecordedEvent, Recorder, to_script};
+pub use scroll::{CLICK_DISTANCE, ScrollSource};
+pub use smooth_motion::{Easing, SmoothMotion};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::{fmt, fs, os::unix::fs::FileExt};
+pub use stream::TextStream;
+pub use text::{TextToken, parse_escapes, type_tokens};
+pub use virtual_device::{
+    keyboard::traits::VirtualKeyboard, pointer::traits::VirtualPointer, touch::traits::VirtualTouch,
+};
+#[cfg(feature = "portals")]
+use virtual_device::{
+    keyboard::{eis::EisKeyboard, portal::PortalKeyboard},
+    pointer::{eis::EisPointer, portal::PortalPointer},
+    touch::{eis::EisTouch, portal::PortalTouch},
+};
 use virtual_device::{
-    keyboard::{traits::VirtualKeyboard, wayland::WaylandKeyboard},
-    pointer::{traits::VirtualPointer, wayland::WaylandPointer},
+    keyboard::{recording::RecordingKeyboard, wayland::WaylandKeyboard},
+    pointer::{recording::RecordingPointer, wayland::WaylandPointer},
 };
 use wayland_client::{
-    Connection, Dispatch, EventQueue, QueueHandle, delegate_dispatch, delegate_noop,
+    Connection, Dispatch, EventQueue, QueueHandle, delegate_noop,
     globals::{GlobalList, GlobalListContents, registry_queue_init},
     protocol::{wl_keyboard, wl_registry, wl_seat},
 };
 use wayland_protocols_wlr::virtual_pointer::v1::client::{
     zwlr_virtual_pointer_manager_v1, zwlr_virtual_pointer_v1,
 };
-use xkbcommon::xkb::KeyDirection;
+use xkbcommon::xkb::{self, KeyDirection};
 
 pub struct KeymapInfo {
     pub format: wl_keyboard::KeymapFormat,
@@ -30,11 +67,55 @@ pub struct KeymapInfo {
     pub size: u32,
 }
 
+impl KeymapInfo {
+    /// # Errors
+    ///
+    /// Keymap couldn't be read or compiled
+    pub fn compile(&self) -> Result<xkb::Keymap> {
+        let mut keymap = vec![0; self.size as usize];
+        fs::File::from(self.fd.try_clone()?).read_exact_at(&mut keymap, 0)?;
+
+        // Compositors include the terminating NUL in the size, which older xkbcommon rejects
+        let len = keymap.iter().position(|b| *b == 0).unwrap_or(keymap.len());
+        keymap.truncate(len);
+        let keymap = String::from_utf8(keymap).map_err(|_| Error::InvalidKeymap)?;
+
+        let xkb_context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        xkb::Keymap::new_from_string(
+            &xkb_context,
+            keymap,
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .ok_or(Error::InvalidKeymap)
+    }
+}
+
 pub struct KeyPress {
     pub keycode: u32,
     pub pressed: KeyDirection,
 }
 
+impl KeyPress {
+    #[must_use]
+    pub const fn new(keycode: xkb::Keycode, pressed: KeyDirection) -> Self {
+        Self {
+            // xkbcommon uses keycodes with an offset of 8, there are none below it
+            keycode: match keycode.raw().checked_sub(8) {
+                Some(keycode) => keycode,
+                None => 0,
+            },
+            pressed,
+        }
+    }
+
+    /// Keycode as used by xkbcommon
+    #[must_use]
+    pub const fn xkb_keycode(&self) -> xkb::Keycode {
+        xkb::Keycode::new(self.keycode.saturating_add(8))
+    }
+}
+
 impl fmt::Debug for KeyPress {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("KeyPress")
@@ -60,26 +141,22 @@ struct State {
     outputs: Outputs,
     key_delay: i32,
     keymap_info: Arc<Mutex<Option<KeymapInfo>>>,
+    keymap_serial: u64,
 }
 
-pub struct Whydotool {
+struct WaylandBackend {
+    conn: Connection,
     seat: Option<wl_seat::WlSeat>,
     event_queue: EventQueue<State>,
     state: State,
-    force_portal: bool,
     globals: GlobalList,
     qh: QueueHandle<State>,
     _wl_keyboard: Option<wl_keyboard::WlKeyboard>,
 }
 
-impl Whydotool {
-    #[cfg(feature = "portals")]
-    /// # Errors
-    ///
-    /// Connection to wayland socket failed
-    pub fn try_new() -> anyhow::Result<Self> {
-        let conn = Connection::connect_to_env()?;
-        let (globals, mut event_queue) = registry_queue_init(&conn)?;
+impl WaylandBackend {
+    fn try_new(conn: &Connection) -> Result<Self> {
+        let (globals, mut event_queue) = registry_queue_init(conn)?;
         let qh = event_queue.handle();
 
         let seat = globals.bind::<wl_seat::WlSeat, _, _>(&qh, 1..=4, ()).ok();
@@ -89,88 +166,204 @@ impl Whydotool {
             key_delay: 0,
             outputs: Outputs::new(&globals, &qh),
             keymap_info: Arc::new(Mutex::new(None)),
+            keymap_serial: 0,
         };
 
         event_queue.dispatch_pending(&mut state)?;
         event_queue.roundtrip(&mut state)?;
 
         Ok(Self {
+            conn: conn.clone(),
             seat,
+            event_queue,
             state,
-            force_portal: false,
             globals,
             qh,
             _wl_keyboard: wl_keyboard,
-            event_queue,
         })
     }
+}
 
-    #[cfg(not(feature = "portals"))]
-    pub fn try_new() -> anyhow::Result<Self> {
-        let conn = Connection::connect_to_env()?;
-        let (globals, mut event_queue) = registry_queue_init(&conn)?;
-        let qh = event_queue.handle();
-
-        let seat = globals.bind::<wl_seat::WlSeat, _, _>(&qh, 1..=4, ()).ok();
-        let wl_keyboard = seat.as_ref().map(|seat| seat.get_keyboard(&qh, ()));
+enum Backend {
+    Wayland(Box<WaylandBackend>),
+    Recording {
+        keymap: xkb::Keymap,
+        recorder: Recorder,
+    },
+}
 
-        let mut state = State {
-            key_delay: 0,
-            outputs: Outputs::new(&globals, &qh),
-            keymap_info: Arc::new(Mutex::new(None)),
-        };
+pub struct Whydotool {
+    backend: Backend,
+    force_portal: bool,
+    #[cfg(feature = "portals")]
+    portal_connection: Option<zbus::blocking::Connection>,
+    #[cfg(feature = "portals")]
+    restore_tokens: Option<RestoreTokens>,
+    #[cfg(feature = "portals")]
+    share_all_outputs: bool,
+}
 
-        event_queue.dispatch_pending(&mut state)?;
-        event_queue.roundtrip(&mut state)?;
+impl Whydotool {
+    /// # Errors
+    ///
+    /// Connection to wayland socket failed
+    pub fn try_new() -> Result<Self> {
+        Self::try_from_connection(&Connection::connect_to_env()?)
+    }
 
+    /// Same as [`Self::try_new`] over an already established connection
+    ///
+    /// # Errors
+    ///
+    /// Initial roundtrip with the compositor failed
+    pub fn try_from_connection(conn: &Connection) -> Result<Self> {
         Ok(Self {
+            backend: Backend::Wayland(Box::new(WaylandBackend::try_new(conn)?)),
             force_portal: false,
-            globals,
-            qh,
-            seat,
-            state,
-            _wl_keyboard: wl_keyboard,
-            event_queue,
+            #[cfg(feature = "portals")]
+            portal_connection: None,
+            #[cfg(feature = "portals")]
+            restore_tokens: RestoreTokens::from_env(),
+            #[cfg(feature = "portals")]
+            share_all_outputs: false,
         })
     }
 
+    /// Backend whose devices append their input to `recorder` instead of sending it,
+    /// using `keymap` as the keyboard layout
+    #[must_use]
+    pub const fn recording(keymap: xkb::Keymap, recorder: Recorder) -> Self {
+        Self {
+            backend: Backend::Recording { keymap, recorder },
+            force_portal: false,
+            #[cfg(feature = "portals")]
+            portal_connection: None,
+            #[cfg(feature = "portals")]
+            restore_tokens: None,
+            #[cfg(feature = "portals")]
+            share_all_outputs: false,
+        }
+    }
+
     #[must_use]
     pub fn key_delay(&self) -> i32 {
-        self.state.key_delay
+        match &self.backend {
+            Backend::Wayland(wayland) => wayland.state.key_delay,
+            Backend::Recording { .. } => 0,
+        }
     }
 
-    pub fn roundtrip(&mut self) -> anyhow::Result<usize> {
-        self.event_queue
-            .roundtrip(&mut self.state)
-            .map_err(|e| anyhow::anyhow!("{e}"))
+    /// Number of keymaps the compositor has sent, keyboards created before it last changed use an outdated one
+    #[must_use]
+    pub fn keymap_serial(&self) -> u64 {
+        match &self.backend {
+            Backend::Wayland(wayland) => wayland.state.keymap_serial,
+            Backend::Recording { .. } => 0,
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Connection to the compositor was lost
+    pub fn roundtrip(&mut self) -> Result<usize> {
+        match &mut self.backend {
+            Backend::Wayland(wayland) => Ok(wayland.event_queue.roundtrip(&mut wayland.state)?),
+            Backend::Recording { .. } => Ok(0),
+        }
+    }
+
+    /// Where the pointer currently is, found by briefly covering every output with a transparent surface
+    ///
+    /// # Errors
+    ///
+    /// Compositor doesn't support layer shell, or the pointer didn't enter any of the surfaces
+    pub fn pointer_position(&mut self) -> Result<PointerPosition> {
+        let Backend::Wayland(wayland) = &mut self.backend else {
+            return Err(Error::UnknownPointerPosition);
+        };
+        let seat = wayland.seat.as_ref().ok_or(Error::NoSeat)?;
+
+        let position = pointer_position::query(
+            &wayland.conn,
+            &wayland.globals,
+            seat,
+            &wayland.state.outputs,
+        )?;
+        wayland.event_queue.roundtrip(&mut wayland.state)?;
+
+        Ok(position)
     }
 
     pub fn force_portal(&mut self, force_portal: bool) {
         self.force_portal = force_portal;
     }
 
+    /// Makes portal sessions share every monitor, so devices can be placed on a named output.
+    /// The user is asked to pick monitors when permission is requested
+    #[cfg(feature = "portals")]
+    pub fn share_all_outputs(&mut self, share_all_outputs: bool) {
+        self.share_all_outputs = share_all_outputs;
+    }
+
+    /// Reaches xdg-desktop-portal over `conn` instead of the session bus
+    #[cfg(feature = "portals")]
+    pub fn portal_connection(&mut self, conn: zbus::blocking::Connection) {
+        self.portal_connection = Some(conn);
+    }
+
+    /// Where portal permission is persisted between runs, `None` asks for it every time.
+    /// Defaults to [`RestoreTokens::from_env`]
+    #[cfg(feature = "portals")]
+    pub fn restore_tokens(&mut self, restore_tokens: Option<RestoreTokens>) {
+        self.restore_tokens = restore_tokens;
+    }
+
     #[cfg(feature = "portals")]
     /// # Errors
     /// Lack of virtual keyboard support in compositor
     /// Lack of `RemoteDesktop` interface support in xdg-desktop-portal
-    pub fn virtual_keyboard(&self) -> anyhow::Result<Box<dyn VirtualKeyboard>> {
-        let keymap_guard = self
+    pub fn virtual_keyboard(&self) -> Result<Box<dyn VirtualKeyboard>> {
+        let wayland = match &self.backend {
+            Backend::Wayland(wayland) => wayland,
+            Backend::Recording { keymap, recorder } => {
+                return Ok(Box::new(RecordingKeyboard::new(keymap, recorder.clone())));
+            }
+        };
+
+        let keymap_guard = wayland
             .state
             .keymap_info
             .lock()
-            .map_err(|e| anyhow::anyhow!("{e}"))?;
+            .unwrap_or_else(PoisonError::into_inner);
         let Some(keymap_info) = keymap_guard.as_ref() else {
-            return Err(anyhow::anyhow!("something went horribly wrong"));
+            return Err(Error::NoKeymap);
         };
 
         if !self.force_portal
-            && let Some(seat) = self.seat.as_ref()
-            && let Ok(ptr) = WaylandKeyboard::try_new(&self.globals, &self.qh, seat, keymap_info)
+            && let Some(seat) = wayland.seat.as_ref()
+            && let Ok(ptr) =
+                WaylandKeyboard::try_new(&wayland.globals, &wayland.qh, seat, keymap_info)
         {
             return Ok(Box::new(ptr));
         }
 
-        let remote_desktop = RemoteDesktop::builder().keyboard(true).try_build()?;
+        let remote_desktop = RemoteDesktop::builder()
+            .keyboard(true)
+            .connection(self.portal_connection.clone())
+            .restore_tokens(self.restore_tokens.clone())
+            .try_build()?;
+
+        // EIS takes events over a socket of its own. The portal's calls are the fallback without it,
+        // but they're refused once the session was connected to EIS
+        if let Ok(socket) = remote_desktop.connect_to_eis() {
+            let eis = Eis::connect(socket, &[eis::KEYBOARD])?;
+            return Ok(Box::new(EisKeyboard::try_new(
+                eis,
+                remote_desktop,
+                keymap_info,
+            )?));
+        }
+
         Ok(Box::new(PortalKeyboard::try_new(
             remote_desktop,
             keymap_info,
@@ -182,20 +375,29 @@ impl Whydotool {
     ///
     /// No seat was found
     /// Keymap information was unavailable
-    pub fn virtual_keyboard(&self) -> anyhow::Result<Box<dyn VirtualKeyboard>> {
-        let Some(seat) = self.seat.as_ref() else {
-            return Err(anyhow::anyhow!("No seat provided for Wayland keyboard"));
+    pub fn virtual_keyboard(&self) -> Result<Box<dyn VirtualKeyboard>> {
+        let wayland = match &self.backend {
+            Backend::Wayland(wayland) => wayland,
+            Backend::Recording { keymap, recorder } => {
+                return Ok(Box::new(RecordingKeyboard::new(keymap, recorder.clone())));
+            }
+        };
+
+        let Some(seat) = wayland.seat.as_ref() else {
+            return Err(Error::NoSeat);
         };
 
-        let keymap_guard = self.state.keymap_info.lock().unwrap();
+        let keymap_guard = wayland
+            .state
+            .keymap_info
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
         let Some(keymap_info) = keymap_guard.as_ref() else {
-            return Err(anyhow::anyhow!(
-                "No keymap information available. Make sure a keyboard is connected and the keymap event has been received."
-            ));
+            return Err(Error::NoKeymap);
         };
         Ok(Box::new(WaylandKeyboard::try_new(
-            &self.globals,
-            &self.qh,
+            &wayland.globals,
+            &wayland.qh,
             seat,
             keymap_info,
         )?))
@@ -206,13 +408,20 @@ impl Whydotool {
     ///
     /// Lack of support for virtual pointer protocol in compositor
     /// Lack of `RemoteDesktop` interface support in xdg-desktop-portal
-    pub fn virtual_pointer(&self) -> anyhow::Result<Box<dyn VirtualPointer>> {
+    pub fn virtual_pointer(&self) -> Result<Box<dyn VirtualPointer>> {
+        let wayland = match &self.backend {
+            Backend::Wayland(wayland) => wayland,
+            Backend::Recording { recorder, .. } => {
+                return Ok(Box::new(RecordingPointer::new(recorder.clone())));
+            }
+        };
+
         if !self.force_portal
             && let Ok(ptr) = WaylandPointer::try_new(
-                &self.globals,
-                &self.qh,
-                self.seat.as_ref(),
-                self.state.outputs.clone(),
+                &wayland.globals,
+                &wayland.qh,
+                wayland.seat.as_ref(),
+                wayland.state.outputs.clone(),
             )
         {
             return Ok(Box::new(ptr));
@@ -221,22 +430,86 @@ impl Whydotool {
         let remote_desktop = RemoteDesktop::builder()
             .pointer(true)
             .screencast(true)
+            .all_monitors(self.share_all_outputs)
+            .connection(self.portal_connection.clone())
+            .restore_tokens(self.restore_tokens.clone())
             .try_build()?;
 
-        let portal_ptr = PortalPointer::new(remote_desktop);
+        if let Ok(socket) = remote_desktop.connect_to_eis() {
+            let eis = Eis::connect(
+                socket,
+                &[
+                    eis::POINTER,
+                    eis::POINTER_ABSOLUTE,
+                    eis::BUTTON,
+                    eis::SCROLL,
+                ],
+            )?;
+            return Ok(Box::new(EisPointer::new(
+                eis,
+                remote_desktop,
+                wayland.state.outputs.clone(),
+            )));
+        }
+
+        let portal_ptr = PortalPointer::new(remote_desktop, wayland.state.outputs.clone());
         Ok(Box::new(portal_ptr))
     }
 
+    #[cfg(feature = "portals")]
+    /// Touchscreen through the portal, Wayland has no protocol for virtual ones
+    ///
+    /// # Errors
+    ///
+    /// Lack of `RemoteDesktop` interface or touchscreen support in xdg-desktop-portal
+    pub fn virtual_touch(&self) -> Result<Box<dyn VirtualTouch>> {
+        let Backend::Wayland(wayland) = &self.backend else {
+            return Err(Error::ProtocolUnsupported("Virtual Touch"));
+        };
+
+        let remote_desktop = RemoteDesktop::builder()
+            .touchscreen(true)
+            .screencast(true)
+            .all_monitors(self.share_all_outputs)
+            .connection(self.portal_connection.clone())
+            .restore_tokens(self.restore_tokens.clone())
+            .try_build()?;
+        if remote_desktop.devices() & portal::remote_desktop::TOUCHSCREEN == 0 {
+            return Err(Error::PortalDeviceUnsupported("touchscreen"));
+        }
+
+        if let Ok(socket) = remote_desktop.connect_to_eis() {
+            let eis = Eis::connect(socket, &[eis::TOUCHSCREEN])?;
+            return Ok(Box::new(EisTouch::new(
+                eis,
+                remote_desktop,
+                wayland.state.outputs.clone(),
+            )));
+        }
+
+        Ok(Box::new(PortalTouch::try_new(
+            remote_desktop,
+            wayland.state.outputs.clone(),
+        )?))
+    }
+
     #[cfg(not(feature = "portals"))]
     /// # Errors
     ///
     /// Lack of support for virtual pointer protocol in compositor
-    pub fn virtual_pointer(&self) -> anyhow::Result<Box<dyn VirtualPointer>> {
+    pub fn virtual_pointer(&self) -> Result<Box<dyn VirtualPointer>> {
+        let wayland = match &self.backend {
+            Backend::Wayland(wayland) => wayland,
+            Backend::Recording { recorder, .. } => {
+                return Ok(Box::new(RecordingPointer::new(recorder.clone())));
+            }
+        };
+
         Ok(Box::new(WaylandPointer::try_new(
-            &self.globals,
-            &self.qh,
-            self.seat.as_ref(),
-            self.state.outputs.clone(),
+            &wayland.globals,
+            &wayland.qh,
+            wayland.seat.as_ref(),
+            wayland.state.outputs.clone(),
         )?))
     }
 }
@@ -252,14 +525,16 @@ impl Dispatch<wl_keyboard::WlKeyboard, ()> for State {
     ) {
         match event {
             wl_keyboard::Event::Keymap { format, fd, size } => {
-                let keymap_info = KeymapInfo {
-                    format: format.into_result().unwrap(),
-                    fd,
-                    size,
+                let Ok(format) = format.into_result() else {
+                    return;
                 };
-                if let Ok(mut keymap_guard) = state.keymap_info.lock() {
-                    *keymap_guard = Some(keymap_info);
-                }
+
+                let keymap_info = KeymapInfo { format, fd, size };
+                *state
+                    .keymap_info
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = Some(keymap_info);
+                state.keymap_serial += 1;
             }
             wl_keyboard::Event::RepeatInfo { rate, delay: _ } => {
                 state.key_delay = ((1.0 / rate as f32) * 1000.) as i32;
@@ -269,7 +544,28 @@ impl Dispatch<wl_keyboard::WlKeyboard, ()> for State {
     }
 }
 
+// Outputs may be plugged in or out while the connection is kept, like by the daemon
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } => state.outputs.add(registry, name, &interface, version, qh),
+            wl_registry::Event::GlobalRemove { name } => state.outputs.remove(name),
+            _ => {}
+        }
+    }
+}
+
 delegate_noop!(State: ignore wl_seat::WlSeat);
-delegate_dispatch!(State: [wl_registry::WlRegistry: GlobalListContents] => State);
 delegate_noop!(State: zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1);
 delegate_noop!(State: zwlr_virtual_pointer_v1::ZwlrVirtualPointerV1);
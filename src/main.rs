@@ -1,56 +1,101 @@
 mod cli;
+mod daemon;
+mod devices;
+mod drag;
+mod record;
+mod script;
 mod stdin;
 
+use anyhow::Context;
 use clap::Parser;
-use cli::{Cli, Commands};
-use libwhydotool::Whydotool;
+#[cfg(feature = "portals")]
+use cli::TouchGesture;
+use cli::{Cli, Commands, KeyAction};
+use devices::Devices;
+#[cfg(feature = "portals")]
+use libwhydotool::RestoreTokens;
+use libwhydotool::{
+    CharKey, Coordinate, KeySequence, Player, SmoothMotion, TextStream, TextToken, Whydotool,
+    parse_escapes, to_script, type_tokens,
+};
+use script::{Script, Step};
 use std::io::Read;
-use std::{fs, io, time::Duration};
+use std::{env, fs, io, path::Path, time::Duration};
 use wayland_client::protocol::wl_pointer::ButtonState;
 use xkbcommon::xkb;
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
-    let mut whydotool = Whydotool::try_new()?;
     #[cfg(feature = "portals")]
-    whydotool.force_portal(cli.force_portal);
+    if let Commands::ForgetPermission = cli.cmd {
+        if let Some(restore_tokens) = RestoreTokens::from_env() {
+            restore_tokens.forget()?;
+        }
+
+        return Ok(());
+    }
+
+    if let Commands::Record { output } = &cli.cmd {
+        let script = to_script(&record::record()?);
+        match output {
+            Some(output) => fs::write(output, script)?,
+            None => print!("{script}"),
+        }
+
+        return Ok(());
+    }
+
+    // The daemon's devices were created without the flags given here
+    if cli.cmd.forwardable() && !cli.device_flags() && daemon::forward(env::args_os().skip(1))? {
+        return Ok(());
+    }
 
-    match cli.cmd {
+    let mut devices = Devices::new(Whydotool::try_new()?);
+    #[cfg(feature = "portals")]
+    devices.force_portal(cli.force_portal);
+
+    if let Commands::Daemon = cli.cmd {
+        return daemon::run(&mut devices);
+    }
+
+    execute(&mut devices, cli.cmd, &Player::default())
+}
+
+/// Runs a command with `devices`, which may be reused by further commands, waiting as `player` schedules
+fn execute(devices: &mut Devices, cmd: Commands, player: &Player) -> anyhow::Result<()> {
+    match cmd {
         Commands::Click {
             repeat,
             next_delay,
             buttons,
+            count,
         } => {
-            let virtual_pointer = whydotool.virtual_pointer()?;
+            let (whydotool, virtual_pointer) = devices.pointer()?;
 
             for _ in 0..repeat {
-                for btn_str in &buttons {
-                    let btn = if btn_str.starts_with("0x") || btn_str.starts_with("0X") {
-                        u8::from_str_radix(&btn_str[2..], 16)?
+                for click in &buttons {
+                    // Only whole clicks are counted, holding a button twice is the same as once
+                    let times = if click.press && click.release {
+                        count
                     } else {
-                        btn_str.parse::<u8>()?
+                        1
                     };
+                    for _ in 0..times {
+                        if click.press {
+                            virtual_pointer.button(click.button, ButtonState::Pressed)?;
+                        }
 
-                    let keycode = u32::from(btn & 0x0f) | 0x110;
-
-                    let down = btn & 0x40 != 0;
-                    let up = btn & 0x80 != 0;
-
-                    if down {
-                        virtual_pointer.button(keycode, ButtonState::Pressed);
-                    }
-
-                    if up {
-                        virtual_pointer.button(keycode, ButtonState::Released);
-                    }
+                        if click.release {
+                            virtual_pointer.button(click.button, ButtonState::Released)?;
+                        }
 
-                    whydotool.roundtrip()?;
+                        whydotool.roundtrip()?;
 
-                    if (btn & 0xC0) == 0
-                        && let Some(delay) = next_delay
-                    {
-                        std::thread::sleep(Duration::from_millis(delay));
+                        // Clicks that neither press nor release are pure delays, as in ydotool
+                        if let Some(delay) = next_delay {
+                            player.sleep(Duration::from_millis(delay));
+                        }
                     }
                 }
             }
@@ -60,36 +105,203 @@ fn main() -> anyhow::Result<()> {
             absolute,
             xpos,
             ypos,
+            output,
+            duration,
+            from,
+            steps,
+            easing,
+            controls,
         } => {
-            let virtual_pointer = whydotool.virtual_pointer()?;
-
-            if wheel {
-                virtual_pointer.scroll(xpos, ypos);
-            } else if absolute {
-                virtual_pointer.motion_absolute(xpos as u32, ypos as u32);
+            devices.use_output(output.as_deref());
+            let (whydotool, virtual_pointer) = devices.pointer()?;
+
+            let smooth_motion = duration.map(|duration| {
+                let smooth_motion = SmoothMotion::new(player.delay(duration)).easing(easing);
+                let smooth_motion = match steps {
+                    Some(steps) => smooth_motion.steps(steps),
+                    None => smooth_motion,
+                };
+                controls
+                    .iter()
+                    .fold(smooth_motion, |smooth_motion, (x, y)| {
+                        smooth_motion.control(*x, *y)
+                    })
+            });
+
+            if absolute {
+                let size = || virtual_pointer.absolute_size(output.as_deref());
+                let (x, y) = resolve_position(size, (xpos, ypos))?;
+
+                match (smooth_motion, output.as_deref()) {
+                    (Some(smooth_motion), output) => {
+                        let Some(from) = from else {
+                            anyhow::bail!(
+                                "Gradual absolute moves need a starting position, given with --from"
+                            );
+                        };
+                        let from = resolve_position(size, from)?;
+
+                        smooth_motion.motion_absolute(
+                            virtual_pointer,
+                            output,
+                            (f64::from(from.0), f64::from(from.1)),
+                            (x, y),
+                            || {
+                                whydotool.roundtrip()?;
+                                Ok(())
+                            },
+                        )?;
+                    }
+                    (None, Some(output)) => virtual_pointer.motion_absolute_output(output, x, y)?,
+                    (None, None) => virtual_pointer.motion_absolute(x, y)?,
+                }
             } else {
-                virtual_pointer.motion(xpos, ypos);
+                let (Some(x), Some(y)) = (xpos.pixels(), ypos.pixels()) else {
+                    anyhow::bail!("Percentages only apply to absolute positions");
+                };
+
+                if wheel {
+                    virtual_pointer.scroll(x, y)?;
+                } else if let Some(smooth_motion) = smooth_motion {
+                    smooth_motion.motion(virtual_pointer, x, y, || {
+                        whydotool.roundtrip()?;
+                        Ok(())
+                    })?;
+                } else {
+                    virtual_pointer.motion(x, y)?;
+                }
             }
 
             whydotool.roundtrip()?;
         }
+        Commands::Scroll {
+            clicks,
+            horizontal,
+            xpos,
+            ypos,
+            source,
+        } => {
+            let (whydotool, virtual_pointer) = devices.pointer()?;
+
+            match clicks {
+                Some(clicks) if horizontal => virtual_pointer.scroll_discrete(clicks, 0)?,
+                Some(clicks) => virtual_pointer.scroll_discrete(0, clicks)?,
+                None => virtual_pointer.scroll_source(
+                    xpos.unwrap_or_default(),
+                    ypos.unwrap_or_default(),
+                    source,
+                )?,
+            }
+
+            whydotool.roundtrip()?;
+        }
+        Commands::Drag {
+            from,
+            to,
+            output,
+            button,
+            duration,
+            steps,
+            easing,
+        } => {
+            devices.use_output(output.as_deref());
+            let (whydotool, virtual_pointer) = devices.pointer()?;
+
+            let size = || virtual_pointer.absolute_size(output.as_deref());
+            let from = resolve_position(size, from)?;
+            let to = resolve_position(size, to)?;
+            let smooth_motion = SmoothMotion::new(player.delay(duration)).easing(easing);
+            let smooth_motion = match steps {
+                Some(steps) => smooth_motion.steps(steps),
+                None => smooth_motion,
+            };
+
+            drag::drag(
+                whydotool,
+                virtual_pointer,
+                output.as_deref(),
+                from,
+                to,
+                button,
+                &smooth_motion,
+            )?;
+        }
+        #[cfg(feature = "portals")]
+        Commands::Touch { gesture, output } => {
+            devices.use_output(output.as_deref());
+            let (whydotool, virtual_touch) = devices.touch()?;
+            let output = output.as_deref();
+            let size = || virtual_touch.size(output);
+            let point = |(x, y): (u32, u32)| (f64::from(x), f64::from(y));
+            let after_step = || {
+                whydotool.roundtrip()?;
+                Ok(())
+            };
+
+            let (from, to, motion) = match gesture {
+                TouchGesture::Tap { position, hold } => {
+                    let position = point(resolve_position(size, position)?);
+                    virtual_touch.down(output, 0, position.0, position.1)?;
+                    player.sleep(hold);
+                    virtual_touch.up(0)?;
+                    return Ok(());
+                }
+                TouchGesture::Swipe { from, to, motion } => (
+                    vec![point(resolve_position(size, from)?)],
+                    vec![point(resolve_position(size, to)?)],
+                    motion,
+                ),
+                TouchGesture::Pinch {
+                    center,
+                    from,
+                    to,
+                    motion,
+                } => {
+                    // Fingers are side by side, each half the distance from the center
+                    let (x, y) = point(resolve_position(size, center)?);
+                    (
+                        vec![(x - from / 2.0, y), (x + from / 2.0, y)],
+                        vec![(x - to / 2.0, y), (x + to / 2.0, y)],
+                        motion,
+                    )
+                }
+            };
+
+            let smooth_motion =
+                SmoothMotion::new(player.delay(motion.duration)).easing(motion.easing);
+            let smooth_motion = match motion.steps {
+                Some(steps) => smooth_motion.steps(steps),
+                None => smooth_motion,
+            };
+            smooth_motion.touch(virtual_touch, output, &from, &to, after_step)?;
+        }
         Commands::Key {
-            key_presses,
+            key_inputs,
             key_delay,
         } => {
-            let mut virtual_keyboard = whydotool.virtual_keyboard()?;
+            let (whydotool, virtual_keyboard) = devices.keyboard()?;
+            let key_delay = key_delay.unwrap_or(whydotool.key_delay() as u64);
+
+            let mut sequence = KeySequence::new();
+            for key_input in key_inputs {
+                let keycodes = virtual_keyboard.keycodes_from_keys(&key_input.keys)?;
 
-            for key_press in key_presses {
+                sequence = match key_input.action {
+                    KeyAction::Press => sequence.press_all(&keycodes),
+                    KeyAction::Release => sequence.release_all(&keycodes),
+                    KeyAction::Tap => sequence.chord(&keycodes),
+                };
+            }
+
+            for key_press in sequence {
                 whydotool.roundtrip()?;
 
-                // xkbcommon uses keycodes with an offset of 8
-                let keycode = xkb::Keycode::new(key_press.keycode + 8);
-                virtual_keyboard.key(keycode, key_press.pressed);
+                virtual_keyboard.key(key_press.xkb_keycode(), key_press.pressed)?;
 
-                std::thread::sleep(Duration::from_millis(
-                    key_delay.unwrap_or(whydotool.key_delay() as u64),
-                ));
+                player.sleep(Duration::from_millis(key_delay));
             }
+
+            whydotool.roundtrip()?;
         }
         Commands::Type {
             strings,
@@ -97,60 +309,74 @@ fn main() -> anyhow::Result<()> {
             key_delay,
             key_hold,
             file,
-            ..
+            escape,
         } => {
-            let mut virtual_keyboard = whydotool.virtual_keyboard()?;
+            let (whydotool, virtual_keyboard) = devices.keyboard()?;
+
+            let escape = escape.map_or(file.is_none(), |escape| escape != 0);
+            let key_hold = player.delay(Duration::from_millis(
+                key_hold.unwrap_or(whydotool.key_delay() as u64),
+            ));
+            let key_delay =
+                Duration::from_millis(key_delay.unwrap_or(whydotool.key_delay() as u64));
+            let next_delay =
+                Duration::from_millis(next_delay.unwrap_or(whydotool.key_delay() as u64));
+
+            let mut type_text = |text: &str| -> libwhydotool::Result<()> {
+                let tokens = if escape {
+                    parse_escapes(text)
+                } else {
+                    text.chars().map(TextToken::Char).collect()
+                };
 
-            let input = match file {
-                Some(file) if file.as_str() == "-" => {
-                    let mut buffer = String::new();
-                    io::stdin().read_line(&mut buffer)?;
+                type_tokens(&mut *virtual_keyboard, &tokens, key_hold, || {
+                    whydotool.roundtrip()?;
+                    player.sleep(key_delay);
+                    Ok(())
+                })?;
 
-                    buffer.lines().map(ToString::to_string).collect()
-                }
-                Some(file) => fs::read_to_string(file)?
-                    .lines()
-                    .map(ToString::to_string)
-                    .collect(),
-                None => strings,
+                Ok(())
             };
 
-            for string in input {
-                for ch in string.chars() {
-                    if let Some((keycode, needs_shift)) = virtual_keyboard.keycode_from_char(ch) {
-                        if needs_shift {
-                            // xkbcommon uses keycodes with an offset of 8
-                            let keycode = xkb::Keycode::new(42 + 8);
-                            virtual_keyboard.key(keycode, xkb::KeyDirection::Down); // Shift down
-                        }
-
-                        virtual_keyboard.key(keycode, xkb::KeyDirection::Down);
-                        std::thread::sleep(Duration::from_millis(
-                            key_hold.unwrap_or(whydotool.key_delay() as u64),
-                        ));
-                        virtual_keyboard.key(keycode, xkb::KeyDirection::Up);
+            let typed = (|| -> anyhow::Result<()> {
+                match file {
+                    Some(file) => {
+                        let reader: Box<dyn Read> = if file.as_str() == "-" {
+                            Box::new(io::stdin().lock())
+                        } else {
+                            Box::new(fs::File::open(file)?)
+                        };
 
-                        if needs_shift {
-                            // xkbcommon uses keycodes with an offset of 8
-                            let keycode = xkb::Keycode::new(42 + 8);
-                            virtual_keyboard.key(keycode, xkb::KeyDirection::Up); // Shift up
+                        // Escapes can't be split safely mid-line, so with them enabled only whole lines are typed
+                        for text in TextStream::new(reader, escape) {
+                            let text = text?;
+                            type_text(&text)?;
+                            if text.ends_with('\n') {
+                                player.sleep(next_delay);
+                            }
+                        }
+                    }
+                    None => {
+                        for string in strings {
+                            type_text(&string)?;
+                            player.sleep(next_delay);
                         }
-
-                        whydotool.roundtrip()?;
-
-                        std::thread::sleep(Duration::from_millis(
-                            key_delay.unwrap_or(whydotool.key_delay() as u64),
-                        ));
                     }
                 }
 
-                std::thread::sleep(Duration::from_millis(
-                    next_delay.unwrap_or(whydotool.key_delay() as u64),
-                ));
-            }
+                Ok(())
+            })();
+
+            // Bound characters are dropped even when typing failed, the daemon keeps the keyboard for later commands.
+            // An error typing is the one reported
+            let restored = virtual_keyboard
+                .restore_keymap()
+                .and_then(|()| whydotool.roundtrip().map(|_| ()));
+            typed?;
+            restored?;
         }
         Commands::Stdin => {
-            let mut virtual_keyboard = whydotool.virtual_keyboard()?;
+            let (_, virtual_keyboard) = devices.keyboard()?;
 
             let terminal = stdin::Terminal::configure()?;
             terminal.set_ctrlc_handler()?;
@@ -168,7 +394,7 @@ fn main() -> anyhow::Result<()> {
 
                 println!("Key code: {} {} {}", buffer[0], buffer[1], buffer[2]);
 
-                let Some((keycode, is_uppercase)) =
+                let Some(char_key) =
                     (if buffer[0] == 27 && buffer[1] == 91 && buffer[2] >= 65 && buffer[2] <= 76 {
                         let key = match buffer[2] {
                             65 => 103, // KEY_UP
@@ -182,7 +408,13 @@ fn main() -> anyhow::Result<()> {
                             _ => continue,
                         };
 
-                        Some((xkb::Keycode::new(key + 8), false))
+                        Some(CharKey {
+                            keycode: xkb::Keycode::new(key + 8),
+                            mods: 0,
+                            layout: virtual_keyboard
+                                .xkb_state()
+                                .serialize_layout(xkb::STATE_LAYOUT_EFFECTIVE),
+                        })
                     } else {
                         virtual_keyboard.keycode_from_char(buffer[0] as char)
                     })
@@ -190,30 +422,108 @@ fn main() -> anyhow::Result<()> {
                     continue;
                 };
 
-                if is_uppercase {
-                    // xkbcommon uses keycodes with an offset of 8
-                    let keycode = xkb::Keycode::new(42 + 8);
-                    virtual_keyboard.key(keycode, xkb::KeyDirection::Down); // Shift down
-                }
-
                 {
-                    if let Some(name) = virtual_keyboard.xkb_state().key_get_one_sym(keycode).name()
+                    if let Some(name) = virtual_keyboard
+                        .xkb_state()
+                        .key_get_one_sym(char_key.keycode)
+                        .name()
                     {
                         println!("  Maps to: {name}");
                     }
                 }
 
-                virtual_keyboard.key(keycode, xkb::KeyDirection::Down);
-                virtual_keyboard.key(keycode, xkb::KeyDirection::Up);
+                virtual_keyboard.type_char_key(&char_key, Duration::ZERO)?;
+            }
+        }
+        Commands::PointerPosition { shell } => {
+            let position = devices.whydotool().pointer_position()?;
+            let output = position.output.as_deref().unwrap_or_default();
+
+            if shell {
+                println!("X={}", position.x);
+                println!("Y={}", position.y);
+                println!("OUTPUT={output}");
+                println!("OUTPUT_X={}", position.output_x);
+                println!("OUTPUT_Y={}", position.output_y);
+            } else {
+                println!(
+                    "x:{} y:{} output:{output} output_x:{} output_y:{}",
+                    position.x, position.y, position.output_x, position.output_y
+                );
+            }
+        }
+        Commands::Run {
+            file,
+            speed,
+            no_delays,
+            jitter,
+            loops,
+        } => {
+            let player = Player::new()
+                .speed(speed)
+                .delays(!no_delays)
+                .jitter(Duration::from_millis(jitter))
+                .loops(loops);
+
+            if file.as_str() == "-" {
+                if player.loop_count() != 1 {
+                    anyhow::bail!("Scripts from stdin can't be looped");
+                }
 
-                if is_uppercase {
-                    // xkbcommon uses keycodes with an offset of 8
-                    let keycode = xkb::Keycode::new(42 + 8);
-                    virtual_keyboard.key(keycode, xkb::KeyDirection::Up); // Shift up
+                // Lines from stdin run as they arrive, sleeps start once they're read
+                for step in Script::new(io::stdin().lock(), Path::new("")) {
+                    match step? {
+                        Step::Sleep(duration) => player.sleep(duration),
+                        Step::Command(cmd) => execute(devices, cmd, &player)?,
+                    }
+                }
+            } else {
+                let dir = Path::new(&file).parent().unwrap_or(Path::new(""));
+                let reader = io::BufReader::new(fs::File::open(&file)?);
+
+                // Whole files are checked before running anything
+                let steps = Script::new(reader, dir).collect::<anyhow::Result<Vec<_>>>()?;
+                let mut played = 0;
+                while player.loop_count() == 0 || played < player.loop_count() {
+                    for step in &steps {
+                        match step {
+                            Step::Sleep(duration) => player.sleep(*duration),
+                            Step::Command(cmd) => execute(devices, cmd.clone(), &player)?,
+                        }
+                    }
+                    played += 1;
                 }
             }
         }
+        Commands::Record { .. } => unreachable!("handled before connecting"),
+        Commands::Daemon => unreachable!("daemon runs commands itself"),
+        #[cfg(feature = "portals")]
+        Commands::ForgetPermission => unreachable!("handled before connecting"),
     }
 
     Ok(())
 }
+
+/// Absolute pixel position of `position`, which may be a percentage of the width and height from `size`
+fn resolve_position(
+    size: impl FnOnce() -> libwhydotool::Result<(u32, u32)>,
+    (x, y): (Coordinate, Coordinate),
+) -> anyhow::Result<(u32, u32)> {
+    // Only percentages need the size, which may not be known
+    let (width, height) =
+        if matches!(x, Coordinate::Percent(_)) || matches!(y, Coordinate::Percent(_)) {
+            size()?
+        } else {
+            (0, 0)
+        };
+
+    let resolve = |coordinate: Coordinate, size| {
+        coordinate.resolve(size).with_context(|| {
+            format!(
+                "Absolute position '{coordinate}' must be whole pixels or a percentage from 0% to 100%"
+            )
+        })
+    };
+
+    Ok((resolve(x, width)?, resolve(y, height)?))
+}
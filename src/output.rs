@@ -1,61 +1,243 @@
-use crate::State;
+use crate::{Error, Result, State};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use wayland_client::{
-    Connection, Dispatch, Proxy, QueueHandle, globals::GlobalList, protocol::wl_output,
+    Connection, Dispatch, Proxy, QueueHandle, delegate_noop,
+    globals::GlobalList,
+    protocol::{wl_output, wl_registry},
 };
+use wayland_protocols::xdg::xdg_output::zv1::client::{zxdg_output_manager_v1, zxdg_output_v1};
 
+/// Outputs of the compositor, kept up to date as events are dispatched. Clones share the same outputs,
+/// so devices holding one see outputs being added, removed or moved
 #[derive(Clone)]
-pub struct Outputs(Vec<Output>);
+pub struct Outputs {
+    outputs: Arc<Mutex<Vec<Output>>>,
+    xdg_output_manager: Option<zxdg_output_manager_v1::ZxdgOutputManagerV1>,
+}
 
 impl Outputs {
     pub fn new(globals: &GlobalList, qh: &QueueHandle<State>) -> Self {
-        let mut outputs = Vec::new();
+        // Without xdg-output the logical geometry is derived from the mode, scale and transform
+        let xdg_output_manager = globals
+            .bind::<zxdg_output_manager_v1::ZxdgOutputManagerV1, _, _>(qh, 1..=3, ())
+            .ok();
+
+        let outputs = Self {
+            outputs: Arc::default(),
+            xdg_output_manager,
+        };
         globals.contents().with_list(|list| {
-            list.iter()
-                .filter(|global| global.interface == wl_output::WlOutput::interface().name)
-                .for_each(|global| {
-                    let wl_output = globals.registry().bind(global.name, global.version, qh, ());
-                    let output = Output::new(wl_output);
-                    outputs.push(output);
-                });
+            for global in list {
+                outputs.add(
+                    globals.registry(),
+                    global.name,
+                    &global.interface,
+                    global.version,
+                    qh,
+                );
+            }
         });
 
-        Self(outputs)
+        outputs
+    }
+
+    /// Binds the global `name` if it's an output
+    pub fn add(
+        &self,
+        registry: &wl_registry::WlRegistry,
+        name: u32,
+        interface: &str,
+        version: u32,
+        qh: &QueueHandle<State>,
+    ) {
+        if interface != wl_output::WlOutput::interface().name {
+            return;
+        }
+
+        let wl_output = registry.bind(name, version.min(4), qh, ());
+        let xdg_output = self
+            .xdg_output_manager
+            .as_ref()
+            .map(|manager| manager.get_xdg_output(&wl_output, qh, ()));
+        self.lock().push(Output::new(name, wl_output, xdg_output));
+    }
+
+    /// Forgets the output bound from the global `name`, if there's one
+    pub fn remove(&self, name: u32) {
+        self.lock().retain(|output| {
+            if output.global != name {
+                return true;
+            }
+
+            if let Some(xdg_output) = &output.xdg_output {
+                xdg_output.destroy();
+            }
+            if output.wl_output.version() >= 3 {
+                output.wl_output.release();
+            }
+            false
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Output>> {
+        self.outputs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Smallest rectangle containing every output, as its top left corner, width and height.
+    /// Absolute positions are relative to that corner, as outputs may be left of or above the origin
+    pub fn bounds(&self) -> (i32, i32, i32, i32) {
+        let outputs = self.lock();
+        let mut outputs = outputs.iter();
+        let Some(first) = outputs.next() else {
+            return (0, 0, 0, 0);
+        };
+
+        let (left, top, right, bottom) = outputs.fold(
+            (
+                first.x,
+                first.y,
+                first.x + first.width,
+                first.y + first.height,
+            ),
+            |(left, top, right, bottom), output| {
+                (
+                    left.min(output.x),
+                    top.min(output.y),
+                    right.max(output.x + output.width),
+                    bottom.max(output.y + output.height),
+                )
+            },
+        );
+
+        (left, top, right - left, bottom - top)
     }
 
     pub fn dimensions(&self) -> (i32, i32) {
-        self.0.iter().fold((0, 0), |(w, h), output| {
-            let output_right = output.x + output.width;
-            let output_bottom = output.y + output.height;
-            (w.max(output_right), h.max(output_bottom))
-        })
+        let (_, _, width, height) = self.bounds();
+        (width, height)
+    }
+
+    /// Absolute position of `(x, y)` relative to the top left corner of the output named `name`
+    ///
+    /// # Errors
+    ///
+    /// No output is named `name`
+    pub fn absolute_position(&self, name: &str, x: u32, y: u32) -> Result<(u32, u32)> {
+        let output = self
+            .find(name)
+            .ok_or_else(|| Error::UnknownOutput(name.into()))?;
+        let (left, top, _, _) = self.bounds();
+
+        // Outputs are never left of or above the bounds
+        Ok((
+            ((output.x - left) as u32).saturating_add(x),
+            ((output.y - top) as u32).saturating_add(y),
+        ))
     }
 
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Output> {
-        self.0.iter_mut()
+    /// Position in the compositor's layout of the absolute position `(x, y)`
+    #[cfg(feature = "portals")]
+    pub fn layout_position(&self, x: u32, y: u32) -> (i64, i64) {
+        let (left, top, _, _) = self.bounds();
+        (
+            i64::from(left) + i64::from(x),
+            i64::from(top) + i64::from(y),
+        )
+    }
+
+    /// Width and height of the output named `name`, or of the bounds of every output
+    ///
+    /// # Errors
+    ///
+    /// No output is named `name`
+    pub fn size(&self, name: Option<&str>) -> Result<(u32, u32)> {
+        let (width, height) = match name {
+            Some(name) => {
+                let output = self
+                    .find(name)
+                    .ok_or_else(|| Error::UnknownOutput(name.into()))?;
+                (output.width, output.height)
+            }
+            None => self.dimensions(),
+        };
+
+        Ok((width as u32, height as u32))
+    }
+
+    pub fn find(&self, name: &str) -> Option<Output> {
+        self.lock()
+            .iter()
+            .find(|output| output.name.as_deref() == Some(name))
+            .cloned()
+    }
+
+    /// Outputs as they are now, in the order they were announced
+    pub fn to_vec(&self) -> Vec<Output> {
+        self.lock().clone()
+    }
+
+    /// Runs `update` on the output matching `predicate`, if there's one
+    fn update(&self, predicate: impl Fn(&Output) -> bool, update: impl FnOnce(&mut Output)) {
+        if let Some(output) = self.lock().iter_mut().find(|output| predicate(output)) {
+            update(output);
+        }
     }
 }
 
+/// Output with its geometry in the compositor's logical layout
 #[derive(Clone)]
 pub struct Output {
+    /// Name of the global it was bound from
+    global: u32,
     pub name: Option<Box<str>>,
     pub wl_output: wl_output::WlOutput,
+    pub xdg_output: Option<zxdg_output_v1::ZxdgOutputV1>,
     pub x: i32,
     pub y: i32,
     pub width: i32,
     pub height: i32,
+    mode: (i32, i32),
+    scale: i32,
+    transform: wl_output::Transform,
+    logical_size: Option<(i32, i32)>,
 }
 
 impl Output {
-    pub const fn new(wl_output: wl_output::WlOutput) -> Self {
+    const fn new(
+        global: u32,
+        wl_output: wl_output::WlOutput,
+        xdg_output: Option<zxdg_output_v1::ZxdgOutputV1>,
+    ) -> Self {
         Self {
+            global,
             name: None,
             wl_output,
+            xdg_output,
             x: 0,
             y: 0,
             width: 0,
             height: 0,
+            mode: (0, 0),
+            scale: 1,
+            transform: wl_output::Transform::Normal,
+            logical_size: None,
         }
     }
+
+    /// Recomputes the logical size, preferring the one reported by xdg-output
+    fn update_size(&mut self) {
+        (self.width, self.height) = self.logical_size.unwrap_or_else(|| {
+            let (width, height) = match self.transform {
+                wl_output::Transform::_90
+                | wl_output::Transform::_270
+                | wl_output::Transform::Flipped90
+                | wl_output::Transform::Flipped270 => (self.mode.1, self.mode.0),
+                _ => self.mode,
+            };
+            let scale = self.scale.max(1);
+            (width / scale, height / scale)
+        });
+    }
 }
 
 impl Dispatch<wl_output::WlOutput, ()> for State {
@@ -67,28 +249,73 @@ impl Dispatch<wl_output::WlOutput, ()> for State {
         _: &Connection,
         _: &QueueHandle<Self>,
     ) {
-        if let Some(output) = state
-            .outputs
-            .iter_mut()
-            .find(|output| output.wl_output == *wl_output)
-        {
-            match event {
+        state.outputs.update(
+            |output| output.wl_output == *wl_output,
+            |output| match event {
                 wl_output::Event::Name { name } => output.name = Some(name.into()),
-                wl_output::Event::Geometry { x, y, .. } => {
-                    output.x = x;
-                    output.y = y;
+                wl_output::Event::Geometry {
+                    x, y, transform, ..
+                } => {
+                    // Position from xdg-output takes precedence, some compositors report 0 here
+                    if output.xdg_output.is_none() {
+                        output.x = x;
+                        output.y = y;
+                    }
+                    if let Ok(transform) = transform.into_result() {
+                        output.transform = transform;
+                    }
+                    output.update_size();
                 }
                 wl_output::Event::Mode {
-                    flags: _,
+                    flags,
                     width,
                     height,
                     refresh: _,
-                } => {
-                    output.width = width;
-                    output.height = height;
+                } if flags
+                    .into_result()
+                    .is_ok_and(|flags| flags.contains(wl_output::Mode::Current)) =>
+                {
+                    output.mode = (width, height);
+                    output.update_size();
+                }
+                wl_output::Event::Scale { factor } => {
+                    output.scale = factor;
+                    output.update_size();
                 }
                 _ => {}
-            }
-        }
+            },
+        );
+    }
+}
+
+impl Dispatch<zxdg_output_v1::ZxdgOutputV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        xdg_output: &zxdg_output_v1::ZxdgOutputV1,
+        event: <zxdg_output_v1::ZxdgOutputV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        state.outputs.update(
+            |output| output.xdg_output.as_ref() == Some(xdg_output),
+            |output| match event {
+                zxdg_output_v1::Event::LogicalPosition { x, y } => {
+                    output.x = x;
+                    output.y = y;
+                }
+                zxdg_output_v1::Event::LogicalSize { width, height } => {
+                    output.logical_size = Some((width, height));
+                    output.update_size();
+                }
+                // wl_output only has names since version 4
+                zxdg_output_v1::Event::Name { name } => {
+                    output.name.get_or_insert_with(|| name.into());
+                }
+                _ => {}
+            },
+        );
     }
 }
+
+delegate_noop!(State: zxdg_output_manager_v1::ZxdgOutputManagerV1);
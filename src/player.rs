@@ -0,0 +1,180 @@
+use crate::{Event, RecordedEvent, Result, VirtualKeyboard, VirtualPointer};
+use std::time::{Duration, Instant};
+use wayland_client::protocol::wl_pointer::ButtonState;
+use xkbcommon::xkb::{KeyDirection, Keycode};
+
+/// Schedules delays between input, scaled by a speed, randomly varied by a jitter or skipped entirely
+#[derive(Debug, Clone, Copy)]
+pub struct Player {
+    speed: f64,
+    delays: bool,
+    jitter: Duration,
+    loops: u32,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            delays: true,
+            jitter: Duration::ZERO,
+            loops: 1,
+        }
+    }
+}
+
+impl Player {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Divides every delay by `speed`, speeds that aren't positive play without delays
+    #[must_use]
+    pub const fn speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Plays as fast as possible when disabled
+    #[must_use]
+    pub const fn delays(mut self, delays: bool) -> Self {
+        self.delays = delays;
+        self
+    }
+
+    /// Shifts every delay by a random amount of up to `jitter` in either direction
+    #[must_use]
+    pub const fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Plays events `loops` times, 0 repeats them until the process is stopped
+    #[must_use]
+    pub const fn loops(mut self, loops: u32) -> Self {
+        self.loops = loops;
+        self
+    }
+
+    #[must_use]
+    pub const fn loop_count(&self) -> u32 {
+        self.loops
+    }
+
+    /// How long to actually wait for a delay of `delay`
+    #[must_use]
+    pub fn delay(&self, delay: Duration) -> Duration {
+        if !self.delays {
+            return Duration::ZERO;
+        }
+
+        let scaled =
+            Duration::try_from_secs_f64(delay.as_secs_f64() / self.speed).unwrap_or(Duration::ZERO);
+        if self.jitter.is_zero() {
+            return scaled;
+        }
+
+        let jitter = rand::random_range(-self.jitter.as_secs_f64()..=self.jitter.as_secs_f64());
+        Duration::try_from_secs_f64(scaled.as_secs_f64() + jitter).unwrap_or(Duration::ZERO)
+    }
+
+    pub fn sleep(&self, delay: Duration) {
+        std::thread::sleep(self.delay(delay));
+    }
+
+    /// Schedule starting now, for waits measured from the start rather than from the end of the previous one
+    #[must_use]
+    pub fn schedule(&self) -> Schedule {
+        Schedule {
+            player: *self,
+            deadline: Instant::now(),
+        }
+    }
+
+    /// Sends events to the devices, keeping the time between them. Waits are measured from the start
+    /// of each loop, so time spent sending doesn't add up. `after_event` runs after every event
+    ///
+    /// # Errors
+    ///
+    /// Sending an event or `after_event` failed
+    pub fn play(
+        &self,
+        events: &[RecordedEvent],
+        virtual_keyboard: &mut dyn VirtualKeyboard,
+        virtual_pointer: &dyn VirtualPointer,
+        mut after_event: impl FnMut() -> Result<()>,
+    ) -> Result<()> {
+        let mut played = 0;
+        while self.loops == 0 || played < self.loops {
+            let mut schedule = self.schedule();
+            let mut previous = events.first().map(|recorded| recorded.time);
+
+            for recorded in events {
+                if let Some(previous) = previous {
+                    schedule.wait(recorded.time.duration_since(previous));
+                }
+                previous = Some(recorded.time);
+
+                send(&recorded.event, virtual_keyboard, virtual_pointer)?;
+                after_event()?;
+            }
+
+            played += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Deadlines of a [`Player`]'s waits. Each wait ends a delay after where the previous one ended,
+/// so time spent between waits, like sending input, doesn't add up
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule {
+    player: Player,
+    deadline: Instant,
+}
+
+impl Schedule {
+    /// Waits until `delay`, as scaled by the player, after the previous deadline
+    pub fn wait(&mut self, delay: Duration) {
+        self.deadline += self.player.delay(delay);
+        std::thread::sleep(self.deadline.saturating_duration_since(Instant::now()));
+    }
+}
+
+fn send(
+    event: &Event,
+    virtual_keyboard: &mut dyn VirtualKeyboard,
+    virtual_pointer: &dyn VirtualPointer,
+) -> Result<()> {
+    match *event {
+        Event::Key { keycode, pressed } => {
+            let direction = if pressed {
+                KeyDirection::Down
+            } else {
+                KeyDirection::Up
+            };
+            // xkbcommon uses keycodes with an offset of 8
+            virtual_keyboard.key(Keycode::new(keycode + 8), direction)?;
+        }
+        Event::Layout(layout) => {
+            virtual_keyboard.lock_layout(layout);
+        }
+        // Contents of replaced keymaps aren't recorded
+        Event::Keymap => {}
+        Event::Button { button, pressed } => {
+            let state = if pressed {
+                ButtonState::Pressed
+            } else {
+                ButtonState::Released
+            };
+            virtual_pointer.button(button, state)?;
+        }
+        Event::Scroll { x, y } => virtual_pointer.scroll(x, y)?,
+        Event::Motion { x, y } => virtual_pointer.motion(x, y)?,
+        Event::MotionAbsolute { x, y } => virtual_pointer.motion_absolute(x, y)?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,258 @@
+use crate::{Error, Result, output::Outputs};
+use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+use std::{
+    os::fd::AsFd,
+    time::{Duration, Instant},
+};
+use wayland_client::{
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle, delegate_noop,
+    globals::GlobalList,
+    protocol::{wl_buffer, wl_compositor, wl_pointer, wl_seat, wl_shm, wl_shm_pool, wl_surface},
+};
+use wayland_protocols_wlr::layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
+
+// How long the pointer has to enter one of the surfaces
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Pointer position in the compositor's logical layout, and within the output it's on
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointerPosition {
+    pub x: f64,
+    pub y: f64,
+    pub output: Option<Box<str>>,
+    pub output_x: f64,
+    pub output_y: f64,
+}
+
+/// Transparent surface covering an output
+struct Cover {
+    surface: wl_surface::WlSurface,
+    layer_surface: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+    buffer: Option<(wl_shm_pool::WlShmPool, wl_buffer::WlBuffer)>,
+}
+
+struct Probe {
+    shm: wl_shm::WlShm,
+    covers: Vec<Cover>,
+    // Index of the entered cover with the position on it
+    entered: Option<(usize, f64, f64)>,
+    error: Option<Error>,
+}
+
+/// Finds the pointer by covering every output with a transparent overlay and waiting for it to enter one
+pub fn query(
+    conn: &Connection,
+    globals: &GlobalList,
+    seat: &wl_seat::WlSeat,
+    outputs: &Outputs,
+) -> Result<PointerPosition> {
+    let mut event_queue = conn.new_event_queue::<Probe>();
+    let qh = event_queue.handle();
+
+    let layer_shell = globals
+        .bind::<zwlr_layer_shell_v1::ZwlrLayerShellV1, _, _>(&qh, 1..=4, ())
+        .map_err(|_| Error::ProtocolUnsupported("Layer Shell"))?;
+    let compositor = globals
+        .bind::<wl_compositor::WlCompositor, _, _>(&qh, 1..=4, ())
+        .map_err(|_| Error::ProtocolUnsupported("Compositor"))?;
+    let shm = globals
+        .bind::<wl_shm::WlShm, _, _>(&qh, 1..=1, ())
+        .map_err(|_| Error::ProtocolUnsupported("Shared Memory"))?;
+    let pointer = seat.get_pointer(&qh, ());
+
+    // Outputs may change meanwhile, the pointer is placed on them as they were covered
+    let outputs = outputs.to_vec();
+    let covers = outputs
+        .iter()
+        .enumerate()
+        .map(|(i, output)| {
+            let surface = compositor.create_surface(&qh, ());
+            let layer_surface = layer_shell.get_layer_surface(
+                &surface,
+                Some(&output.wl_output),
+                zwlr_layer_shell_v1::Layer::Overlay,
+                "whydotool".to_string(),
+                &qh,
+                i,
+            );
+            layer_surface.set_anchor(
+                zwlr_layer_surface_v1::Anchor::Top
+                    | zwlr_layer_surface_v1::Anchor::Bottom
+                    | zwlr_layer_surface_v1::Anchor::Left
+                    | zwlr_layer_surface_v1::Anchor::Right,
+            );
+            // Covers panels too
+            layer_surface.set_exclusive_zone(-1);
+            surface.commit();
+
+            Cover {
+                surface,
+                layer_surface,
+                buffer: None,
+            }
+        })
+        .collect();
+
+    let mut probe = Probe {
+        shm,
+        covers,
+        entered: None,
+        error: None,
+    };
+
+    let result = wait_for_enter(&mut event_queue, &mut probe);
+
+    for cover in std::mem::take(&mut probe.covers) {
+        cover.layer_surface.destroy();
+        cover.surface.destroy();
+        if let Some((pool, buffer)) = cover.buffer {
+            buffer.destroy();
+            pool.destroy();
+        }
+    }
+    if pointer.version() >= 3 {
+        pointer.release();
+    }
+    event_queue.roundtrip(&mut probe)?;
+
+    let (i, output_x, output_y) = result?;
+    let output = outputs.get(i).ok_or(Error::UnknownPointerPosition)?;
+
+    Ok(PointerPosition {
+        x: f64::from(output.x) + output_x,
+        y: f64::from(output.y) + output_y,
+        output: output.name.clone(),
+        output_x,
+        output_y,
+    })
+}
+
+fn wait_for_enter(
+    event_queue: &mut EventQueue<Probe>,
+    probe: &mut Probe,
+) -> Result<(usize, f64, f64)> {
+    let deadline = Instant::now() + TIMEOUT;
+
+    loop {
+        event_queue.dispatch_pending(probe)?;
+        if let Some(error) = probe.error.take() {
+            return Err(error);
+        }
+        if let Some(entered) = probe.entered {
+            return Ok(entered);
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(Error::UnknownPointerPosition);
+        }
+
+        event_queue
+            .flush()
+            .map_err(wayland_client::DispatchError::Backend)?;
+        let Some(guard) = event_queue.prepare_read() else {
+            continue;
+        };
+
+        let timeout = PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX);
+        let mut fds = [PollFd::new(guard.connection_fd(), PollFlags::POLLIN)];
+        if poll(&mut fds, timeout).map_err(std::io::Error::from)? > 0 {
+            guard
+                .read()
+                .map_err(wayland_client::DispatchError::Backend)?;
+        }
+    }
+}
+
+impl Probe {
+    /// Attaches a fully transparent buffer of the configured size, so the surface gets mapped
+    fn attach_buffer(
+        &mut self,
+        i: usize,
+        width: u32,
+        height: u32,
+        qh: &QueueHandle<Self>,
+    ) -> Result<()> {
+        let stride = width * 4;
+        let size = stride * height;
+
+        let file = tempfile::tempfile()?;
+        file.set_len(u64::from(size))?;
+
+        let pool = self.shm.create_pool(file.as_fd(), size as i32, qh, ());
+        let buffer = pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            stride as i32,
+            wl_shm::Format::Argb8888,
+            qh,
+            (),
+        );
+
+        let cover = &mut self.covers[i];
+        cover.surface.attach(Some(&buffer), 0, 0);
+        cover.surface.commit();
+        if let Some((pool, buffer)) = cover.buffer.replace((pool, buffer)) {
+            buffer.destroy();
+            pool.destroy();
+        }
+
+        Ok(())
+    }
+}
+
+impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, usize> for Probe {
+    fn event(
+        state: &mut Self,
+        layer_surface: &zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        i: &usize,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_layer_surface_v1::Event::Configure {
+            serial,
+            width,
+            height,
+        } = event
+        {
+            layer_surface.ack_configure(serial);
+            if let Err(error) = state.attach_buffer(*i, width.max(1), height.max(1), qh) {
+                state.error = Some(error);
+            }
+        }
+    }
+}
+
+impl Dispatch<wl_pointer::WlPointer, ()> for Probe {
+    fn event(
+        state: &mut Self,
+        _: &wl_pointer::WlPointer,
+        event: wl_pointer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_pointer::Event::Enter {
+            surface,
+            surface_x,
+            surface_y,
+            ..
+        } = event
+            && let Some(i) = state
+                .covers
+                .iter()
+                .position(|cover| cover.surface == surface)
+        {
+            state.entered = Some((i, surface_x, surface_y));
+        }
+    }
+}
+
+delegate_noop!(Probe: ignore wl_shm::WlShm);
+delegate_noop!(Probe: ignore wl_surface::WlSurface);
+delegate_noop!(Probe: ignore wl_compositor::WlCompositor);
+delegate_noop!(Probe: ignore wl_shm_pool::WlShmPool);
+delegate_noop!(Probe: ignore wl_buffer::WlBuffer);
+delegate_noop!(Probe: ignore zwlr_layer_shell_v1::ZwlrLayerShellV1);
@@ -1,4 +1,6 @@
 pub mod remote_desktop;
 mod request;
+pub mod restore_tokens;
 pub mod screencast;
+pub mod stream;
 mod util;
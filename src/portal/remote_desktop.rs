@@ -1,17 +1,29 @@
 use crate::portal::screencast::ScreenCast;
 
-use super::{request, screencast, util};
-use anyhow::Context;
+use super::{request, restore_tokens::RestoreTokens, screencast, util};
+use crate::{Error, Result};
 use std::collections::HashMap;
 use wayland_client::protocol::wl_pointer;
 use xkbcommon::xkb::{KeyDirection, Keycode};
 use zbus::zvariant::{self, OwnedFd};
 
+// Permission is kept until revoked by the user
+const PERSIST_UNTIL_REVOKED: u32 = 2;
+
+// Device type bits
+pub const KEYBOARD: u32 = 1;
+pub const POINTER: u32 = 2;
+pub const TOUCHSCREEN: u32 = 4;
+
 #[derive(Default)]
 pub struct RemoteDesktopBuilder {
     enable_keyboard: bool,
     enable_pointer: bool,
+    enable_touchscreen: bool,
     enable_screencast: bool,
+    all_monitors: bool,
+    connection: Option<zbus::blocking::Connection>,
+    restore_tokens: Option<RestoreTokens>,
 }
 
 impl RemoteDesktopBuilder {
@@ -29,80 +41,160 @@ impl RemoteDesktopBuilder {
         self
     }
 
+    pub fn touchscreen(mut self, enable: bool) -> Self {
+        self.enable_touchscreen = enable;
+        self
+    }
+
     pub fn screencast(mut self, enable: bool) -> Self {
         self.enable_screencast = enable;
         self
     }
 
-    pub fn try_build(self) -> anyhow::Result<RemoteDesktop> {
-        let conn = zbus::blocking::Connection::session()?;
-        let remote_desktop_proxy = RemoteDesktopProxyBlocking::new(&conn)?;
+    /// Shares every monitor rather than one the user picks, so streams of specific outputs can be found
+    pub fn all_monitors(mut self, enable: bool) -> Self {
+        self.all_monitors = enable;
+        self
+    }
 
-        let device_types = remote_desktop_proxy.available_device_types()?;
+    /// Bus to reach the portal on, the session bus if `None`
+    pub fn connection(mut self, connection: Option<zbus::blocking::Connection>) -> Self {
+        self.connection = connection;
+        self
+    }
 
-        let keyboard_supported = (device_types & 1) != 0;
-        let pointer_supported = (device_types & 2) != 0;
+    /// Persists the permission granted to the session with tokens kept in `restore_tokens`
+    pub fn restore_tokens(mut self, restore_tokens: Option<RestoreTokens>) -> Self {
+        self.restore_tokens = restore_tokens;
+        self
+    }
 
-        let session_token = util::SessionToken::default();
+    /// Sessions with different devices can't restore each other
+    fn session_name(&self) -> String {
+        let devices = [
+            (self.enable_keyboard, "keyboard"),
+            (self.enable_pointer, "pointer"),
+            (self.enable_touchscreen, "touchscreen"),
+            (self.enable_screencast, "screencast"),
+            (self.enable_screencast && self.all_monitors, "monitors"),
+        ];
+
+        devices
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, device)| *device)
+            .collect::<Vec<_>>()
+            .join("-")
+    }
 
-        let session_path = remote_desktop_proxy
-            .create_session([("session_handle_token", session_token.into())].into())?;
+    pub fn try_build(self) -> Result<RemoteDesktop> {
+        let session_name = self.session_name();
+        let conn = match self.connection {
+            Some(conn) => conn,
+            None => zbus::blocking::Connection::session()?,
+        };
+        let remote_desktop_proxy = RemoteDesktopProxyBlocking::new(&conn)?;
 
-        let mut request = request::Request::try_new(&conn, &session_path)?;
-        let session_handle = request.get_session_handle();
+        let device_types = remote_desktop_proxy.available_device_types()?;
 
         let mut selected_device_mask: u32 = 0;
-        if self.enable_keyboard && keyboard_supported {
-            selected_device_mask |= 1;
+        if self.enable_keyboard {
+            selected_device_mask |= KEYBOARD;
+        }
+        if self.enable_pointer {
+            selected_device_mask |= POINTER;
+        }
+        if self.enable_touchscreen {
+            selected_device_mask |= TOUCHSCREEN;
         }
-        if self.enable_pointer && pointer_supported {
-            selected_device_mask |= 2;
+        // Unsupported devices aren't selected, a session without any would fail on first use
+        selected_device_mask &= device_types;
+        if selected_device_mask == 0 {
+            return Err(Error::PortalDeviceUnsupported(if self.enable_touchscreen {
+                "touchscreen"
+            } else if self.enable_pointer {
+                "pointer"
+            } else {
+                "keyboard"
+            }));
         }
 
-        remote_desktop_proxy.select_devices(
-            &session_handle,
-            [("types", selected_device_mask.into())].into(),
+        let session_token = util::SessionToken::default();
+
+        let results = request::call(
+            &conn,
+            [("session_handle_token", session_token.into())].into(),
+            |options| remote_desktop_proxy.create_session(options),
         )?;
 
-        request.next_response().unwrap();
+        let session_handle = results
+            .get("session_handle")
+            .and_then(|value| match &**value {
+                zvariant::Value::Str(s) => zvariant::OwnedObjectPath::try_from(s.as_str()).ok(),
+                _ => None,
+            })
+            .ok_or(Error::PortalResponse)?;
+
+        let mut options = HashMap::from([("types", selected_device_mask.into())]);
+        // Restoring a remote desktop session restores its screencast sources as well
+        if let Some(restore_tokens) = &self.restore_tokens {
+            options.insert("persist_mode", PERSIST_UNTIL_REVOKED.into());
+            if let Some(restore_token) = restore_tokens.load(&session_name) {
+                options.insert("restore_token", restore_token.into());
+            }
+        }
+
+        request::call(&conn, options, |options| {
+            remote_desktop_proxy.select_devices(&session_handle, options)
+        })?;
 
         let screencast = if self.enable_screencast {
             let screencast =
-                screencast::ScreenCast::try_new(&conn, &mut request, session_handle.clone())?;
+                screencast::ScreenCast::try_new(&conn, session_handle.clone(), self.all_monitors)?;
 
             Some(screencast)
         } else {
             None
         };
 
-        remote_desktop_proxy.start(
-            &session_handle,
-            "",
+        let results = request::call(
+            &conn,
             [("devices", selected_device_mask.into())].into(),
+            |options| remote_desktop_proxy.start(&session_handle, "", options),
         )?;
 
-        let res = request.next_response().unwrap();
-        let args = res.args()?;
+        // Tokens are single use, a new one is returned for every session
+        if let Some(restore_tokens) = &self.restore_tokens
+            && let Some(restore_token) = results
+                .get("restore_token")
+                .and_then(|value| String::try_from(value.try_clone().ok()?).ok())
+        {
+            restore_tokens.store(&session_name, &restore_token)?;
+        }
 
-        let streams: Option<Vec<(u32, HashMap<String, zvariant::OwnedValue>)>> = args
-            .results()
+        // Users may grant fewer devices than were selected
+        let devices = results
+            .get("devices")
+            .and_then(|value| u32::try_from(&**value).ok())
+            .unwrap_or(selected_device_mask);
+
+        let streams: Option<Vec<(u32, HashMap<String, zvariant::OwnedValue>)>> = results
             .get("streams")
-            .and_then(|v| v.to_owned().try_into().ok());
-
-        if args.response == 0 {
-            Ok(RemoteDesktop {
-                streams,
-                screencast,
-                session_handle,
-                proxy: remote_desktop_proxy,
-            })
-        } else {
-            anyhow::bail!("Remote desktop session start was rejected by the system")
-        }
+            .and_then(|v| v.try_clone().ok())
+            .and_then(|v| v.try_into().ok());
+
+        Ok(RemoteDesktop {
+            devices,
+            streams,
+            screencast,
+            session_handle,
+            proxy: remote_desktop_proxy,
+        })
     }
 }
 
 pub struct RemoteDesktop {
+    devices: u32,
     streams: Option<Vec<(u32, HashMap<String, zvariant::OwnedValue>)>>,
     screencast: Option<screencast::ScreenCast>,
     session_handle: zbus::zvariant::OwnedObjectPath,
@@ -114,15 +206,16 @@ impl RemoteDesktop {
         RemoteDesktopBuilder::new()
     }
 
+    /// Bitmask of the device types granted to the session
+    pub const fn devices(&self) -> u32 {
+        self.devices
+    }
+
     pub fn streams(&self) -> Option<&Vec<(u32, HashMap<String, zvariant::OwnedValue>)>> {
         self.streams.as_ref()
     }
 
-    pub fn notify_keyboard_keycode(
-        &self,
-        key: Keycode,
-        state: &KeyDirection,
-    ) -> anyhow::Result<()> {
+    pub fn notify_keyboard_keycode(&self, key: Keycode, state: &KeyDirection) -> Result<()> {
         let raw_state = match state {
             KeyDirection::Down => 1,
             KeyDirection::Up => 0,
@@ -138,11 +231,7 @@ impl RemoteDesktop {
         Ok(())
     }
 
-    pub fn notify_pointer_button(
-        &self,
-        button: i32,
-        state: wl_pointer::ButtonState,
-    ) -> anyhow::Result<()> {
+    pub fn notify_pointer_button(&self, button: i32, state: wl_pointer::ButtonState) -> Result<()> {
         self.proxy.notify_pointer_button(
             &self.session_handle,
             HashMap::new(),
@@ -153,26 +242,37 @@ impl RemoteDesktop {
         Ok(())
     }
 
-    pub fn notify_pointer_axis(&self, xpos: f32, ypos: f32) -> anyhow::Result<()> {
-        self.proxy
-            .notify_pointer_axis(&self.session_handle, HashMap::new(), xpos, ypos)?;
+    /// `finish` ends a series of scrolls, like fingers being lifted from a touchpad
+    pub fn notify_pointer_axis(&self, xpos: f32, ypos: f32, finish: bool) -> Result<()> {
+        self.proxy.notify_pointer_axis(
+            &self.session_handle,
+            HashMap::from([("finish", finish.into())]),
+            xpos,
+            ypos,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn notify_pointer_axis_discrete(&self, axis: wl_pointer::Axis, steps: i32) -> Result<()> {
+        self.proxy.notify_pointer_axis_discrete(
+            &self.session_handle,
+            HashMap::new(),
+            axis.into(),
+            steps,
+        )?;
 
         Ok(())
     }
 
-    pub fn notify_pointer_motion(&self, xpos: f32, ypos: f32) -> anyhow::Result<()> {
+    pub fn notify_pointer_motion(&self, xpos: f32, ypos: f32) -> Result<()> {
         self.proxy
             .notify_pointer_motion(&self.session_handle, HashMap::new(), xpos, ypos)?;
 
         Ok(())
     }
 
-    pub fn notify_pointer_motion_absolute(
-        &self,
-        xpos: f32,
-        ypos: f32,
-        node_id: u32,
-    ) -> anyhow::Result<()> {
+    pub fn notify_pointer_motion_absolute(&self, xpos: f32, ypos: f32, node_id: u32) -> Result<()> {
         self.proxy.notify_pointer_motion_absolute(
             &self.session_handle,
             HashMap::new(),
@@ -184,12 +284,51 @@ impl RemoteDesktop {
         Ok(())
     }
 
-    pub fn open_pipewire_remote(&self) -> anyhow::Result<OwnedFd> {
+    pub fn notify_touch_down(&self, node_id: u32, slot: u32, xpos: f64, ypos: f64) -> Result<()> {
+        self.proxy.notify_touch_down(
+            &self.session_handle,
+            HashMap::new(),
+            node_id,
+            slot,
+            xpos,
+            ypos,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn notify_touch_motion(&self, node_id: u32, slot: u32, xpos: f64, ypos: f64) -> Result<()> {
+        self.proxy.notify_touch_motion(
+            &self.session_handle,
+            HashMap::new(),
+            node_id,
+            slot,
+            xpos,
+            ypos,
+        )?;
+
+        Ok(())
+    }
+
+    pub fn notify_touch_up(&self, slot: u32) -> Result<()> {
+        self.proxy
+            .notify_touch_up(&self.session_handle, HashMap::new(), slot)?;
+
+        Ok(())
+    }
+
+    /// Socket to emulate input on with the EI protocol, only available since version 2 of the portal
+    pub fn connect_to_eis(&self) -> Result<OwnedFd> {
+        Ok(self
+            .proxy
+            .connect_to_eis(&self.session_handle, HashMap::new())?)
+    }
+
+    pub fn open_pipewire_remote(&self) -> Result<OwnedFd> {
         self.screencast
             .as_ref()
-            .map(ScreenCast::open_pipewire_remote)
-            .context("")
-            .flatten()
+            .ok_or(Error::NoStream)
+            .and_then(ScreenCast::open_pipewire_remote)
     }
 }
 
@@ -253,6 +392,48 @@ pub trait RemoteDesktop {
         dy: f32,
     ) -> zbus::Result<()>;
 
+    fn notify_pointer_axis_discrete(
+        &self,
+        session_handle: &zbus::zvariant::OwnedObjectPath,
+        options: HashMap<&str, zbus::zvariant::Value<'_>>,
+        axis: u32,
+        steps: i32,
+    ) -> zbus::Result<()>;
+
+    fn notify_touch_down(
+        &self,
+        session_handle: &zbus::zvariant::OwnedObjectPath,
+        options: HashMap<&str, zbus::zvariant::Value<'_>>,
+        stream: u32,
+        slot: u32,
+        x: f64,
+        y: f64,
+    ) -> zbus::Result<()>;
+
+    fn notify_touch_motion(
+        &self,
+        session_handle: &zbus::zvariant::OwnedObjectPath,
+        options: HashMap<&str, zbus::zvariant::Value<'_>>,
+        stream: u32,
+        slot: u32,
+        x: f64,
+        y: f64,
+    ) -> zbus::Result<()>;
+
+    fn notify_touch_up(
+        &self,
+        session_handle: &zbus::zvariant::OwnedObjectPath,
+        options: HashMap<&str, zbus::zvariant::Value<'_>>,
+        slot: u32,
+    ) -> zbus::Result<()>;
+
+    #[zbus(name = "ConnectToEIS")]
+    fn connect_to_eis(
+        &self,
+        session_handle: &zbus::zvariant::OwnedObjectPath,
+        options: HashMap<&str, zbus::zvariant::Value<'_>>,
+    ) -> zbus::Result<OwnedFd>;
+
     fn notify_keyboard_keycode(
         &self,
         session_handle: &zbus::zvariant::OwnedObjectPath,
@@ -1,48 +1,59 @@
-use std::collections::HashMap;
-use zbus::zvariant::OwnedObjectPath;
+use super::util::HandleToken;
+use crate::{Error, Result};
+use std::{collections::HashMap, sync::mpsc, thread, time::Duration};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
 
-pub struct Request {
-    stream: ResponseIterator,
-    session_handle: OwnedObjectPath,
-}
+// Responses may wait on the user to accept a consent dialog
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(120);
 
-impl Request {
-    pub fn try_new(
-        conn: &zbus::blocking::Connection,
-        session_path: &OwnedObjectPath,
-    ) -> anyhow::Result<Self> {
-        let proxy = RequestProxyBlocking::builder(conn)
-            .path(session_path)?
-            .build()?;
-
-        let mut stream = proxy.receive_response()?;
-        let session_handle = stream
-            .next()
-            .unwrap()
-            .args()
-            .ok()
-            .and_then(|response| response.results.get("session_handle").cloned())
-            .and_then(|value| {
-                let zbus::zvariant::Value::Str(s) = value else {
-                    unreachable!()
-                };
-                zbus::zvariant::OwnedObjectPath::try_from(s.as_str()).ok()
-            })
-            .unwrap();
-
-        Ok(Self {
-            stream,
-            session_handle,
-        })
-    }
+/// Results of an accepted portal request
+pub type Results = HashMap<String, OwnedValue>;
 
-    pub fn get_session_handle(&self) -> OwnedObjectPath {
-        self.session_handle.clone()
-    }
+/// Makes a portal method call and waits for the response to the request it creates.
+/// `call` receives `options` with a `handle_token` added, so the request path is known
+/// and subscribed to before the call is made, and its response can't be missed
+pub fn call<'a>(
+    conn: &zbus::blocking::Connection,
+    mut options: HashMap<&'a str, Value<'a>>,
+    call: impl FnOnce(HashMap<&'a str, Value<'a>>) -> zbus::Result<OwnedObjectPath>,
+) -> Result<Results> {
+    let handle_token = HandleToken::default();
+    let request_path = handle_token.request_path(conn)?;
+
+    let proxy = RequestProxyBlocking::builder(conn)
+        .path(request_path)?
+        .build()?;
 
-    pub fn next_response(&mut self) -> Option<Response> {
-        self.stream.next()
+    // Blocking signal iterators can't time out, so the response is forwarded from a thread.
+    // Requests respond once, the thread ends with it
+    let mut stream = proxy.receive_response()?;
+    let (sender, responses) = mpsc::channel();
+    thread::spawn(move || {
+        if let Some(response) = stream.next() {
+            let _ = sender.send(response);
+        }
+    });
+
+    options.insert("handle_token", handle_token.into());
+    call(options)?;
+
+    let response = responses
+        .recv_timeout(RESPONSE_TIMEOUT)
+        .map_err(|e| match e {
+            mpsc::RecvTimeoutError::Timeout => Error::PortalTimeout,
+            mpsc::RecvTimeoutError::Disconnected => Error::PortalResponse,
+        })?;
+
+    let args = response.args()?;
+    if args.response != 0 {
+        return Err(Error::PortalRejected);
     }
+
+    args.results
+        .iter()
+        .map(|(key, value)| Ok(((*key).to_string(), value.try_to_owned()?)))
+        .collect::<zbus::zvariant::Result<_>>()
+        .map_err(|e| Error::DBus(e.into()))
 }
 
 #[zbus::proxy(
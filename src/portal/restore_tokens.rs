@@ -0,0 +1,90 @@
+use crate::Result;
+use std::{
+    env,
+    fs::{self, DirBuilder, OpenOptions, Permissions},
+    io::{self, Write},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt},
+    path::PathBuf,
+};
+
+/// Directory keeping portal restore tokens, so permission granted once is reused by later sessions
+#[derive(Debug, Clone)]
+pub struct RestoreTokens {
+    dir: PathBuf,
+}
+
+impl RestoreTokens {
+    #[must_use]
+    pub const fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// `$XDG_STATE_HOME/whydotool`, falling back to `~/.local/state/whydotool`
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        let state_home = env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+
+        Some(Self::new(state_home.join("whydotool")))
+    }
+
+    /// Token stored for sessions named `name`
+    #[must_use]
+    pub fn load(&self, name: &str) -> Option<String> {
+        fs::read_to_string(self.path(name))
+            .ok()
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+    }
+
+    /// Stores `token` readable by the user only, it lets sessions inject input without asking
+    ///
+    /// # Errors
+    ///
+    /// Token couldn't be written
+    pub fn store(&self, name: &str, token: &str) -> Result<()> {
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&self.dir)?;
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(self.path(name))?;
+        // Modes only apply to new files, tokens stored before may be readable by others
+        file.set_permissions(Permissions::from_mode(0o600))?;
+        file.write_all(token.as_bytes())?;
+        Ok(())
+    }
+
+    /// Forgets every stored token, so the next session asks for permission again
+    ///
+    /// # Errors
+    ///
+    /// A token couldn't be removed
+    pub fn forget(&self) -> Result<()> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "token") {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.token"))
+    }
+}
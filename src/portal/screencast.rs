@@ -1,7 +1,9 @@
 use super::request;
-use anyhow::Context;
+use crate::Result;
 use std::collections::HashMap;
-use zbus::zvariant::{OwnedFd, OwnedObjectPath};
+use zbus::zvariant::{OwnedFd, OwnedObjectPath, Value};
+
+const MONITOR: u32 = 1;
 
 pub struct ScreenCast {
     proxy: ScreenCastProxyBlocking<'static>,
@@ -11,14 +13,23 @@ pub struct ScreenCast {
 impl ScreenCast {
     pub fn try_new(
         conn: &zbus::blocking::Connection,
-        request: &mut request::Request,
         session_handle: OwnedObjectPath,
-    ) -> anyhow::Result<Self> {
+        all_monitors: bool,
+    ) -> Result<Self> {
         let screencast_proxy = ScreenCastProxyBlocking::new(conn)?;
 
-        screencast_proxy.select_sources(&session_handle, HashMap::new())?;
-
-        request.next_response().context("Response not found")?;
+        // Monitors can be picked together, so pointers can be placed on a specific one
+        let options = if all_monitors {
+            HashMap::from([
+                ("types", Value::from(MONITOR)),
+                ("multiple", Value::from(true)),
+            ])
+        } else {
+            HashMap::new()
+        };
+        request::call(conn, options, |options| {
+            screencast_proxy.select_sources(&session_handle, options)
+        })?;
 
         Ok(Self {
             proxy: screencast_proxy,
@@ -26,7 +37,7 @@ impl ScreenCast {
         })
     }
 
-    pub fn open_pipewire_remote(&self) -> anyhow::Result<OwnedFd> {
+    pub fn open_pipewire_remote(&self) -> Result<OwnedFd> {
         Ok(self
             .proxy
             .open_pipe_wire_remote(&self.session_handle, HashMap::new())?)
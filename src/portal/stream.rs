@@ -0,0 +1,152 @@
+use super::remote_desktop::RemoteDesktop;
+use crate::{Error, Result, output::Outputs};
+use pipewire as pw;
+use pw::{context, main_loop, properties::properties, spa, stream::StreamState};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use zbus::zvariant::OwnedValue;
+
+// Longest wait for the streams to start once connected
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Stream of the monitor named `output`, or the first one
+pub fn find<'a>(
+    remote_desktop: &'a RemoteDesktop,
+    outputs: &Outputs,
+    output: Option<&str>,
+) -> Result<&'a (u32, HashMap<String, OwnedValue>)> {
+    let streams = remote_desktop.streams().ok_or(Error::NoStream)?;
+    let Some(output) = output else {
+        return streams.first().ok_or(Error::NoStream);
+    };
+
+    let output = outputs
+        .find(output)
+        .ok_or_else(|| Error::UnknownOutput(output.into()))?;
+
+    // Streams don't carry output names, monitors are told apart by where they're placed
+    streams
+        .iter()
+        .find(|(_, properties)| property(properties, "position") == Some((output.x, output.y)))
+        .ok_or(Error::NoStream)
+}
+
+/// Stream of the monitor at `(x, y)` in the compositor's layout, with the position relative to that monitor
+pub fn at(remote_desktop: &RemoteDesktop, x: i64, y: i64) -> Result<(u32, (i64, i64))> {
+    let streams = remote_desktop.streams().ok_or(Error::NoStream)?;
+
+    streams
+        .iter()
+        .find_map(|(node_id, properties)| {
+            let (left, top) = property(properties, "position")?;
+            let (width, height) = property(properties, "size")?;
+            let (x, y) = (x - i64::from(left), y - i64::from(top));
+            ((0..i64::from(width)).contains(&x) && (0..i64::from(height)).contains(&y))
+                .then_some((*node_id, (x, y)))
+        })
+        .ok_or(Error::NoStream)
+}
+
+/// Width and height of the stream with `properties`
+pub fn size(properties: &HashMap<String, OwnedValue>) -> Result<(u32, u32)> {
+    let (width, height) = property(properties, "size").ok_or(Error::UnknownSize)?;
+
+    Ok((width as u32, height as u32))
+}
+
+fn property(properties: &HashMap<String, OwnedValue>, name: &str) -> Option<(i32, i32)> {
+    properties
+        .get(name)
+        .and_then(|value| value.try_clone().ok())
+        .and_then(|value| <(i32, i32)>::try_from(value).ok())
+}
+
+/// Every stream of a session, consumed for as long as this lives. The portal only handles events placed on
+/// streams that are being consumed
+pub struct Consumer {
+    mainloop: main_loop::MainLoopRc,
+    _context: context::ContextRc,
+    streams: Vec<pw::stream::StreamRc>,
+}
+
+impl Consumer {
+    /// Connects to the streams of `remote_desktop`, returning once they're all streaming
+    ///
+    /// # Errors
+    ///
+    /// There are no streams, connecting to them failed, or they didn't start in time
+    pub fn connect(remote_desktop: &RemoteDesktop) -> Result<Self> {
+        let node_ids: Vec<_> = remote_desktop
+            .streams()
+            .ok_or(Error::NoStream)?
+            .iter()
+            .map(|(node_id, _)| *node_id)
+            .collect();
+
+        pw::init();
+
+        let pw_fd = remote_desktop.open_pipewire_remote()?;
+
+        let mainloop = main_loop::MainLoopRc::new(None)?;
+
+        let context = context::ContextRc::new(&mainloop, None)?;
+
+        let core = context.connect_fd_rc(pw_fd.into(), None)?;
+
+        let streams = node_ids
+            .into_iter()
+            .map(|node_id| {
+                let stream = pw::stream::StreamRc::new(
+                    core.clone(),
+                    "whydotool",
+                    properties! {
+                        *pipewire::keys::MEDIA_TYPE => "Video",
+                        *pipewire::keys::MEDIA_CATEGORY => "Capture",
+                        *pipewire::keys::MEDIA_ROLE => "Screen",
+                    },
+                )?;
+                stream.connect(
+                    spa::utils::Direction::Input,
+                    Some(node_id),
+                    pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+                    &mut [],
+                )?;
+
+                Ok(stream)
+            })
+            .collect::<Result<_>>()?;
+
+        let consumer = Self {
+            mainloop,
+            _context: context,
+            streams,
+        };
+        consumer.wait_streaming()?;
+
+        Ok(consumer)
+    }
+
+    fn wait_streaming(&self) -> Result<()> {
+        let deadline = Instant::now() + START_TIMEOUT;
+        while !self
+            .streams
+            .iter()
+            .all(|stream| stream.state() == StreamState::Streaming)
+        {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::StreamTimeout);
+            }
+            self.mainloop.loop_().iterate(remaining);
+        }
+
+        Ok(())
+    }
+
+    /// Handles what PipeWire sent since last time without blocking, so the streams keep going
+    pub fn dispatch(&self) {
+        self.mainloop.loop_().iterate(Duration::ZERO);
+    }
+}
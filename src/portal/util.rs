@@ -1,18 +1,21 @@
 use rand::{Rng, distr::Alphanumeric};
-use zbus::zvariant::Value;
+use zbus::zvariant::{OwnedObjectPath, Value};
+
+fn random_token() -> String {
+    let token: String = rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(10)
+        .map(char::from)
+        .collect();
+
+    format!("whydotool_{token}")
+}
 
 pub struct SessionToken(String);
 
 impl Default for SessionToken {
     fn default() -> Self {
-        let token: String = rand::rng()
-            .sample_iter(&Alphanumeric)
-            .take(10)
-            .map(char::from)
-            .collect();
-        let session_token = format!("whydotool_{token}");
-
-        Self(session_token)
+        Self(random_token())
     }
 }
 
@@ -21,3 +24,33 @@ impl From<SessionToken> for Value<'_> {
         Value::from(token.0)
     }
 }
+
+/// Token picking the object path of a portal request
+pub struct HandleToken(String);
+
+impl HandleToken {
+    /// Path the portal creates the request at when called over `conn`
+    pub fn request_path(&self, conn: &zbus::blocking::Connection) -> zbus::Result<OwnedObjectPath> {
+        let sender = conn
+            .unique_name()
+            .map(|name| name.trim_start_matches(':').replace('.', "_"))
+            .unwrap_or_default();
+
+        Ok(OwnedObjectPath::try_from(format!(
+            "/org/freedesktop/portal/desktop/request/{sender}/{}",
+            self.0
+        ))?)
+    }
+}
+
+impl Default for HandleToken {
+    fn default() -> Self {
+        Self(random_token())
+    }
+}
+
+impl From<HandleToken> for Value<'_> {
+    fn from(token: HandleToken) -> Self {
+        Value::from(token.0)
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::bail;
+use libwhydotool::{EvdevDecoder, KEY_MAX, RecordedEvent};
+use nix::libc;
+use std::{fs, io::Read, mem, os::fd::AsRawFd, sync::mpsc, thread, time::Instant};
+
+type Sender = mpsc::Sender<Option<RecordedEvent>>;
+
+// `EVIOCGPROP` and `EVIOCGBIT(EV_KEY)` from `linux/input.h`
+nix::ioctl_read_buf!(eviocgprop, b'E', 0x09, u8);
+nix::ioctl_read_buf!(eviocgbit_key, b'E', 0x21, u8);
+
+const INPUT_PROP_POINTER: usize = 0x00;
+const BTN_TOOL_FINGER: usize = 0x145;
+
+/// Records keys, buttons, relative and touchpad motion from every readable `/dev/input/event*` device until Ctrl+C
+pub fn record() -> anyhow::Result<Vec<RecordedEvent>> {
+    let (sender, receiver) = mpsc::channel();
+
+    let mut devices = 0;
+    for entry in fs::read_dir("/dev/input")? {
+        let path = entry?.path();
+        let is_event_device = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("event"));
+        if !is_event_device {
+            continue;
+        }
+
+        // Devices the user can't read are skipped
+        let Ok(device) = fs::File::open(&path) else {
+            continue;
+        };
+
+        let sender = sender.clone();
+        thread::spawn(move || read_device(device, &sender));
+        devices += 1;
+    }
+
+    if devices == 0 {
+        bail!(
+            "No readable input devices in /dev/input, reading them usually requires being in the `input` group"
+        );
+    }
+
+    ctrlc::set_handler(move || {
+        let _ = sender.send(None);
+    })?;
+
+    eprintln!("Recording from {devices} devices, press Ctrl+C to stop");
+
+    let mut events = Vec::new();
+    while let Ok(Some(event)) = receiver.recv() {
+        events.push(event);
+    }
+
+    // Devices are read on separate threads
+    events.sort_by_key(|recorded| recorded.time);
+
+    Ok(events)
+}
+
+fn read_device(mut device: fs::File, sender: &Sender) {
+    let mut buffer = vec![0; mem::size_of::<libc::input_event>() * 64];
+    let mut decoder = EvdevDecoder::new().touchpad(is_touchpad(&device));
+
+    loop {
+        let len = match device.read(&mut buffer) {
+            Ok(0) | Err(_) => return,
+            Ok(len) => len,
+        };
+        let time = Instant::now();
+
+        for event in decoder.decode(&buffer[..len]) {
+            if sender.send(Some(RecordedEvent { time, event })).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Whether the device's absolute positions are a finger moving the pointer, rather than a tablet or joystick
+fn is_touchpad(device: &fs::File) -> bool {
+    let has_bit = |bits: &[u8], bit: usize| {
+        bits.get(bit / 8)
+            .is_some_and(|byte| byte & (1 << (bit % 8)) != 0)
+    };
+
+    let mut props = [0; 4];
+    // SAFETY: the buffer outlives the call, which writes at most its length
+    let pointer = unsafe { eviocgprop(device.as_raw_fd(), &mut props) }
+        .is_ok_and(|_| has_bit(&props, INPUT_PROP_POINTER));
+
+    let mut keys = [0; KEY_MAX as usize / 8 + 1];
+    // SAFETY: as above
+    let finger = unsafe { eviocgbit_key(device.as_raw_fd(), &mut keys) }
+        .is_ok_and(|_| has_bit(&keys, BTN_TOOL_FINGER));
+
+    pointer || finger
+}
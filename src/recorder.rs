@@ -0,0 +1,167 @@
+use crate::{Key, button_name};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    mem,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+use xkbcommon::xkb;
+
+// Consecutive pointer movement within this interval is merged into one line
+const MOTION_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Input sent through a recording backend
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Key with its evdev keycode
+    Key {
+        keycode: u32,
+        pressed: bool,
+    },
+    /// Active layout was locked to the given index
+    Layout(xkb::LayoutIndex),
+    /// Keymap was replaced, either with extra characters bound or with the original one
+    Keymap,
+    Button {
+        button: u32,
+        pressed: bool,
+    },
+    Scroll {
+        x: f64,
+        y: f64,
+    },
+    Motion {
+        x: f64,
+        y: f64,
+    },
+    MotionAbsolute {
+        x: u32,
+        y: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedEvent {
+    pub time: Instant,
+    pub event: Event,
+}
+
+/// Shared log of events sent by devices of a [`crate::Whydotool::recording`] backend
+#[derive(Debug, Clone, Default)]
+pub struct Recorder(Arc<Mutex<Vec<RecordedEvent>>>);
+
+impl Recorder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record(&self, event: Event) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(RecordedEvent {
+                time: Instant::now(),
+                event,
+            });
+    }
+
+    /// Events recorded so far, oldest first
+    #[must_use]
+    pub fn events(&self) -> Vec<RecordedEvent> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn clear(&self) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+/// Writes events as a script for `whydotool run`, with `sleep` lines reproducing their timing.
+///
+/// Keys released without being pressed, like the Return that started a recording,
+/// and keys still held at the end, like the Ctrl+C that stopped it, are left out
+#[must_use]
+pub fn to_script(events: &[RecordedEvent]) -> String {
+    let mut keep = vec![true; events.len()];
+    let mut held = HashMap::new();
+    for (i, recorded) in events.iter().enumerate() {
+        if let Event::Key { keycode, pressed } = recorded.event {
+            if pressed {
+                held.entry(keycode).or_insert(i);
+            } else if held.remove(&keycode).is_none() {
+                keep[i] = false;
+            }
+        }
+    }
+    for i in held.into_values() {
+        keep[i] = false;
+    }
+
+    let mut events = events
+        .iter()
+        .zip(keep)
+        .filter_map(|(recorded, keep)| keep.then_some(recorded))
+        .peekable();
+
+    let mut script = String::new();
+    let Some(start) = events.peek().map(|recorded| recorded.time) else {
+        return script;
+    };
+    let mut slept = 0;
+
+    while let Some(recorded) = events.next() {
+        let line = match recorded.event {
+            Event::Key { keycode, pressed } => {
+                format!(
+                    "key -d 0 {}:{}",
+                    Key::from_evdev(keycode),
+                    u8::from(pressed)
+                )
+            }
+            Event::Button { button, pressed } => {
+                let state = if pressed { "down" } else { "up" };
+                format!("click {}:{state}", button_name(button))
+            }
+            Event::Motion { mut x, mut y } | Event::Scroll { mut x, mut y } => {
+                let kind = mem::discriminant(&recorded.event);
+                while let Some(next) = events.next_if(|next| {
+                    mem::discriminant(&next.event) == kind
+                        && next.time.duration_since(recorded.time) < MOTION_INTERVAL
+                }) {
+                    if let Event::Motion { x: dx, y: dy } | Event::Scroll { x: dx, y: dy } =
+                        next.event
+                    {
+                        x += dx;
+                        y += dy;
+                    }
+                }
+
+                if matches!(recorded.event, Event::Scroll { .. }) {
+                    format!("mousemove -w -x {x} -y {y}")
+                } else {
+                    format!("mousemove -x {x} -y {y}")
+                }
+            }
+            Event::MotionAbsolute { x, y } => format!("mousemove -a -x {x} -y {y}"),
+            Event::Layout(_) | Event::Keymap => continue,
+        };
+
+        // Sleeps are measured from the start, so rounding doesn't accumulate
+        let elapsed = recorded.time.duration_since(start).as_millis();
+        if elapsed > slept {
+            let _ = writeln!(script, "sleep {}", elapsed - slept);
+            slept = elapsed;
+        }
+        let _ = writeln!(script, "{line}");
+    }
+
+    script
+}
@@ -0,0 +1,145 @@
+use crate::cli::{Cli, Commands};
+use anyhow::Context;
+use clap::Parser;
+use std::{
+    io::{BufRead, Lines},
+    iter,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Line of a script
+#[derive(Clone)]
+pub enum Step {
+    Sleep(Duration),
+    Command(Commands),
+}
+
+/// Steps of a script with one command per line, blank lines and lines starting with `#` are skipped
+pub struct Script<R> {
+    lines: Lines<R>,
+    line: usize,
+    dir: PathBuf,
+}
+
+impl<R: BufRead> Script<R> {
+    /// `dir` is what files given to `type -f` are relative to
+    pub fn new(reader: R, dir: &Path) -> Self {
+        Self {
+            lines: reader.lines(),
+            line: 0,
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn parse(&self, line: &str) -> anyhow::Result<Step> {
+        let words = split_words(line)?;
+
+        if words.first().map(String::as_str) == Some("sleep") {
+            let [_, ms] = words.as_slice() else {
+                anyhow::bail!("sleep takes a single duration in ms");
+            };
+            let ms = ms
+                .parse()
+                .with_context(|| format!("Invalid sleep duration '{ms}'"))?;
+
+            return Ok(Step::Sleep(Duration::from_millis(ms)));
+        }
+
+        let cli = Cli::try_parse_from(iter::once("whydotool".to_string()).chain(words))
+            // Only the first line is relevant, the rest is usage
+            .map_err(|e| {
+                let message = e.to_string();
+                let first_line = message.lines().next().unwrap_or_default();
+                anyhow::anyhow!("{}", first_line.trim_start_matches("error: "))
+            })?;
+
+        let mut cmd = cli.cmd;
+        if !cmd.scriptable() {
+            anyhow::bail!("Command can't be used in a script");
+        }
+
+        if let Commands::Type {
+            file: Some(file), ..
+        } = &mut cmd
+        {
+            *file = self.dir.join(&*file).to_string_lossy().into_owned();
+        }
+
+        Ok(Step::Command(cmd))
+    }
+}
+
+impl<R: BufRead> Iterator for Script<R> {
+    type Item = anyhow::Result<Step>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            self.line += 1;
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            return Some(
+                self.parse(line)
+                    .with_context(|| format!("Line {}", self.line)),
+            );
+        }
+    }
+}
+
+/// Splits a line into words like a shell would: single quotes keep everything literally,
+/// double quotes keep everything but `\"` and `\\`, and outside quotes a backslash escapes any character
+fn split_words(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            ' ' | '\t' => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => word.push(ch),
+                        None => anyhow::bail!("Unterminated single quote"),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(ch @ ('"' | '\\')) => word.push(ch),
+                            Some(ch) => {
+                                word.push('\\');
+                                word.push(ch);
+                            }
+                            None => anyhow::bail!("Unterminated double quote"),
+                        },
+                        Some(ch) => word.push(ch),
+                        None => anyhow::bail!("Unterminated double quote"),
+                    }
+                }
+            }
+            '\\' => {
+                let escaped = chars.next().context("Trailing backslash")?;
+                word.get_or_insert_default().push(escaped);
+            }
+            ch => word.get_or_insert_default().push(ch),
+        }
+    }
+
+    words.extend(word);
+    Ok(words)
+}
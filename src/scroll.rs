@@ -0,0 +1,31 @@
+use std::str::FromStr;
+
+/// Scroll distance of one wheel click, as libinput reports it
+pub const CLICK_DISTANCE: f64 = 15.0;
+
+/// Device a scroll comes from, which decides how clients react to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScrollSource {
+    /// Wheel, moving in clicks
+    Wheel,
+    /// Fingers on a touchpad, which clients may keep scrolling kinetically once they are lifted
+    Finger,
+    /// Continuous movement without a stop, like a trackpoint
+    #[default]
+    Continuous,
+}
+
+impl FromStr for ScrollSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wheel" => Ok(Self::Wheel),
+            "finger" => Ok(Self::Finger),
+            "continuous" => Ok(Self::Continuous),
+            _ => Err(format!(
+                "Unknown scroll source '{s}', expected wheel, finger or continuous"
+            )),
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{Result, VirtualPointer, VirtualTouch};
+use std::{
+    io,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+// Steps used when none are given. They're paced by time, not by the compositor's frames
+const STEP_INTERVAL: Duration = Duration::from_millis(8);
+// Longest wait between checks of the interruption flag
+const INTERRUPT_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How progress along a path speeds up and slows down over time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Progress along the path after `t` of the time, both between 0 and 1
+    #[must_use]
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t.powi(3),
+            Self::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Self::EaseInOut if t < 0.5 => 4.0 * t.powi(3),
+            Self::EaseInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+        }
+    }
+}
+
+impl FromStr for Easing {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Self::Linear),
+            "ease-in" => Ok(Self::EaseIn),
+            "ease-out" => Ok(Self::EaseOut),
+            "ease-in-out" => Ok(Self::EaseInOut),
+            _ => Err(format!(
+                "Unknown easing '{s}', expected linear, ease-in, ease-out or ease-in-out"
+            )),
+        }
+    }
+}
+
+/// Pointer movement spread over a series of motion events along a line, or a Bezier curve
+/// when control points are given
+#[derive(Debug, Clone)]
+pub struct SmoothMotion {
+    duration: Duration,
+    steps: Option<u32>,
+    easing: Easing,
+    controls: Vec<(f64, f64)>,
+    interrupted: Option<&'static AtomicBool>,
+}
+
+impl SmoothMotion {
+    #[must_use]
+    pub const fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            steps: None,
+            easing: Easing::Linear,
+            controls: Vec::new(),
+            interrupted: None,
+        }
+    }
+
+    /// Number of motion events, one every 8ms of the duration by default
+    #[must_use]
+    pub const fn steps(mut self, steps: u32) -> Self {
+        self.steps = Some(steps);
+        self
+    }
+
+    #[must_use]
+    pub const fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Adds a Bezier control point, relative to where the movement starts
+    #[must_use]
+    pub fn control(mut self, x: f64, y: f64) -> Self {
+        self.controls.push((x, y));
+        self
+    }
+
+    /// Stops the movement with an `Interrupted` error once `interrupted` is set, also while waiting between steps
+    #[must_use]
+    pub const fn interruptible(mut self, interrupted: &'static AtomicBool) -> Self {
+        self.interrupted = Some(interrupted);
+        self
+    }
+
+    fn step_count(&self) -> u32 {
+        self.steps
+            .unwrap_or_else(|| (self.duration.as_millis() / STEP_INTERVAL.as_millis()) as u32)
+            .max(1)
+    }
+
+    /// Points the pointer passes through going from `from` to `to`, excluding `from`
+    #[must_use]
+    pub fn path(&self, from: (f64, f64), to: (f64, f64)) -> Vec<(f64, f64)> {
+        let mut points = vec![from];
+        points.extend(self.controls.iter().map(|(x, y)| (from.0 + x, from.1 + y)));
+        points.push(to);
+
+        let steps = self.step_count();
+        (1..=steps)
+            .map(|step| {
+                bezier(
+                    &points,
+                    self.easing.apply(f64::from(step) / f64::from(steps)),
+                )
+            })
+            .collect()
+    }
+
+    /// Moves the pointer by `x`, `y`. `after_step` runs after every motion event
+    ///
+    /// # Errors
+    ///
+    /// Sending a motion event or `after_step` failed
+    pub fn motion(
+        &self,
+        virtual_pointer: &dyn VirtualPointer,
+        x: f64,
+        y: f64,
+        mut after_step: impl FnMut() -> Result<()>,
+    ) -> Result<()> {
+        let mut previous = (0.0, 0.0);
+        self.play(self.path(previous, (x, y)), |point| {
+            virtual_pointer.motion(point.0 - previous.0, point.1 - previous.1)?;
+            previous = point;
+            after_step()
+        })
+    }
+
+    /// Moves the pointer from `from` to the absolute position `to`, both relative to `output` if it's given.
+    /// `after_step` runs after every motion event
+    ///
+    /// # Errors
+    ///
+    /// Sending a motion event or `after_step` failed
+    pub fn motion_absolute(
+        &self,
+        virtual_pointer: &dyn VirtualPointer,
+        output: Option<&str>,
+        from: (f64, f64),
+        to: (u32, u32),
+        mut after_step: impl FnMut() -> Result<()>,
+    ) -> Result<()> {
+        let to = (f64::from(to.0), f64::from(to.1));
+        self.play(self.path(from, to), |(x, y)| {
+            // Curves may bend past the top or left edge
+            let (x, y) = (x.round().max(0.0) as u32, y.round().max(0.0) as u32);
+            match output {
+                Some(output) => virtual_pointer.motion_absolute_output(output, x, y)?,
+                None => virtual_pointer.motion_absolute(x, y)?,
+            }
+            after_step()
+        })
+    }
+
+    /// Moves fingers at once from each of `from` to the position at the same index of `to`, on `output` or the first one.
+    /// Fingers are put down at the start, in slots by their order, and lifted at the end even if moving them failed.
+    /// `after_step` runs after every step moving all fingers
+    ///
+    /// # Errors
+    ///
+    /// Sending a touch or `after_step` failed
+    pub fn touch(
+        &self,
+        virtual_touch: &dyn VirtualTouch,
+        output: Option<&str>,
+        from: &[(f64, f64)],
+        to: &[(f64, f64)],
+        mut after_step: impl FnMut() -> Result<()>,
+    ) -> Result<()> {
+        let fingers: Vec<_> = (0..).zip(from.iter().zip(to)).collect();
+
+        let mut touching = || {
+            for (slot, (from, _)) in &fingers {
+                virtual_touch.down(output, *slot, from.0, from.1)?;
+            }
+            after_step()?;
+
+            let paths: Vec<_> = fingers
+                .iter()
+                .map(|(_, (from, to))| self.path(**from, **to))
+                .collect();
+            let steps = (0..self.step_count() as usize)
+                .map(|step| paths.iter().map(|path| path[step]).collect::<Vec<_>>())
+                .collect();
+            self.play(steps, |points: Vec<(f64, f64)>| {
+                for (slot, (x, y)) in (0..).zip(points) {
+                    virtual_touch.motion(output, slot, x, y)?;
+                }
+                after_step()
+            })
+        };
+        let touched = touching();
+
+        // Every finger is lifted, even past one that failed
+        let mut lifted = Ok(());
+        for (slot, _) in &fingers {
+            let up = virtual_touch.up(*slot);
+            lifted = lifted.and(up);
+        }
+
+        touched.and(lifted)
+    }
+
+    /// Sends each point at its share of the duration
+    fn play<T>(&self, path: Vec<T>, mut send: impl FnMut(T) -> Result<()>) -> Result<()> {
+        let start = Instant::now();
+        let steps = path.len() as u32;
+
+        for (step, point) in (1..).zip(path) {
+            self.sleep_until(start + self.duration * step / steps)?;
+            send(point)?;
+        }
+
+        Ok(())
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Result<()> {
+        loop {
+            if self
+                .interrupted
+                .is_some_and(|interrupted| interrupted.load(Ordering::SeqCst))
+            {
+                return Err(io::Error::from(io::ErrorKind::Interrupted).into());
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(());
+            }
+            thread::sleep(remaining.min(INTERRUPT_INTERVAL));
+        }
+    }
+}
+
+/// Point at `t` along the Bezier curve through `points`, by de Casteljau's algorithm
+fn bezier(points: &[(f64, f64)], t: f64) -> (f64, f64) {
+    let mut points = points.to_vec();
+    while points.len() > 1 {
+        points = points
+            .windows(2)
+            .map(|pair| {
+                (
+                    pair[0].0 + (pair[1].0 - pair[0].0) * t,
+                    pair[0].1 + (pair[1].1 - pair[0].1) * t,
+                )
+            })
+            .collect();
+    }
+
+    points[0]
+}
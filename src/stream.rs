@@ -0,0 +1,72 @@
+use std::io::{self, Read};
+
+/// Yields text from a reader as it arrives, a line at most at a time. UTF-8 characters are never split,
+/// and when `whole_lines` is set lines aren't either
+pub struct TextStream<R> {
+    reader: R,
+    pending: Vec<u8>,
+    whole_lines: bool,
+    eof: bool,
+}
+
+impl<R: Read> TextStream<R> {
+    #[must_use]
+    pub const fn new(reader: R, whole_lines: bool) -> Self {
+        Self {
+            reader,
+            pending: Vec::new(),
+            whole_lines,
+            eof: false,
+        }
+    }
+
+    fn complete_len(&self) -> usize {
+        let valid_len = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            // Invalid bytes are replaced rather than waited on
+            Err(e) => e.valid_up_to() + e.error_len().unwrap_or(0),
+        };
+
+        match self.pending[..valid_len]
+            .iter()
+            .position(|byte| *byte == b'\n')
+        {
+            Some(newline) => newline + 1,
+            None if self.whole_lines => 0,
+            None => valid_len,
+        }
+    }
+}
+
+impl<R: Read> Iterator for TextStream<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buffer = [0u8; 4096];
+
+        loop {
+            let len = match self.complete_len() {
+                // Whatever is left at the end is yielded, even an unfinished line or character
+                0 if self.eof => self.pending.len(),
+                len => len,
+            };
+
+            if len > 0 {
+                let text = String::from_utf8_lossy(&self.pending[..len]).into_owned();
+                self.pending.drain(..len);
+                return Some(Ok(text));
+            }
+
+            if self.eof {
+                return None;
+            }
+
+            match self.reader.read(&mut buffer) {
+                Ok(0) => self.eof = true,
+                Ok(read) => self.pending.extend_from_slice(&buffer[..read]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{
+    Error, Result, VirtualKeyboard,
+    key::{Key, KeySequence, parse_chord},
+};
+use std::time::Duration;
+
+/// Piece of text to be typed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextToken {
+    Char(char),
+    /// Key chord embedded in the text, e.g. `\{ctrl+a}`
+    Keys(Vec<Key>),
+}
+
+/// Splits `text` into tokens, expanding ydotool escapes `\n`, `\t` and `\\`,
+/// and key chords written as `\{Return}` or `\{ctrl+a}`.
+/// Unknown or unterminated escapes are typed as is
+#[must_use]
+pub fn parse_escapes(text: &str) -> Vec<TextToken> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+
+    while let Some(ch) = rest.chars().next() {
+        rest = &rest[ch.len_utf8()..];
+
+        if ch != '\\' {
+            tokens.push(TextToken::Char(ch));
+            continue;
+        }
+
+        let (token, escape_len) = match rest.chars().next() {
+            Some('n') => (TextToken::Char('\n'), 1),
+            Some('t') => (TextToken::Char('\t'), 1),
+            Some('\\') => (TextToken::Char('\\'), 1),
+            Some('{') => rest[1..]
+                .split_once('}')
+                .and_then(|(chord, _)| Some((parse_chord(chord).ok()?, chord.len() + 2)))
+                .map_or((TextToken::Char('\\'), 0), |(keys, len)| {
+                    (TextToken::Keys(keys), len)
+                }),
+            _ => (TextToken::Char('\\'), 0),
+        };
+
+        tokens.push(token);
+        rest = &rest[escape_len..];
+    }
+
+    tokens
+}
+
+/// Types `tokens`, binding characters missing from the keymap first and holding each key for `key_hold`.
+/// `after_token` runs once every token has been sent, e.g. to flush and wait between keys
+///
+/// # Errors
+///
+/// A chord contains an unknown key, a character can't be typed even with a keymap of its own,
+/// or the backend failed to send input
+pub fn type_tokens(
+    virtual_keyboard: &mut dyn VirtualKeyboard,
+    tokens: &[TextToken],
+    key_hold: Duration,
+    mut after_token: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            TextToken::Char(ch) => {
+                if virtual_keyboard.keycode_from_char(*ch).is_none() {
+                    let chars = tokens[i..]
+                        .iter()
+                        .filter_map(|token| match token {
+                            TextToken::Char(ch) => Some(*ch),
+                            TextToken::Keys(_) => None,
+                        })
+                        .collect::<Vec<_>>();
+                    virtual_keyboard.map_chars(&chars)?;
+                }
+
+                let char_key = virtual_keyboard
+                    .keycode_from_char(*ch)
+                    .ok_or(Error::UntypableChar(*ch))?;
+
+                if !virtual_keyboard.type_char_key(&char_key, key_hold)? {
+                    return Err(Error::UntypableChar(*ch));
+                }
+            }
+            TextToken::Keys(keys) => {
+                let keycodes = virtual_keyboard.keycodes_from_keys(keys)?;
+                for key_press in KeySequence::new().chord(&keycodes) {
+                    virtual_keyboard.key(key_press.xkb_keycode(), key_press.pressed)?;
+                }
+            }
+        }
+
+        after_token()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use super::traits::VirtualKeyboard;
+use crate::{
+    KeymapInfo, Result,
+    eis::{self, Arg, Eis},
+    portal::remote_desktop::RemoteDesktop,
+};
+use xkbcommon::xkb::{self, KeyDirection, Keycode};
+
+pub struct EisKeyboard {
+    xkb_state: xkb::State,
+    // Closes the connection before the session it came from
+    eis: Eis,
+    _remote_desktop: RemoteDesktop,
+}
+
+impl EisKeyboard {
+    pub fn try_new(
+        eis: Eis,
+        remote_desktop: RemoteDesktop,
+        keymap_info: &KeymapInfo,
+    ) -> Result<Self> {
+        let xkb_state = xkb::State::new(&keymap_info.compile()?);
+
+        Ok(Self {
+            xkb_state,
+            eis,
+            _remote_desktop: remote_desktop,
+        })
+    }
+}
+
+impl VirtualKeyboard for EisKeyboard {
+    fn xkb_state(&mut self) -> &mut xkb::State {
+        &mut self.xkb_state
+    }
+
+    fn key(&mut self, key: Keycode, state: KeyDirection) -> Result<()> {
+        let pressed = matches!(state, KeyDirection::Down);
+        self.xkb_state.update_key(key, state);
+
+        // EI takes evdev codes, xkb ones are offset by 8
+        self.eis.request(
+            eis::KEYBOARD,
+            1,
+            &[Arg::Uint(key.raw() - 8), Arg::Uint(u32::from(pressed))],
+        )
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{Error, Result, key::CharKey};
+use xkbcommon::xkb::{self, KEYMAP_COMPILE_NO_FLAGS, KeyDirection, Keycode};
+
+// https://lists.x.org/archives/wayland-devel/2021-December/042056.html
+/// Finds the key, modifiers and layout producing `c`, preferring `active_layout` and the lowest shift level
+pub fn char_key(keymap: &xkb::Keymap, active_layout: xkb::LayoutIndex, c: char) -> Option<CharKey> {
+    // Newlines are typed with Return rather than Linefeed
+    let target_keysym = if c == '\n' {
+        xkb::Keysym::Return
+    } else {
+        xkb::utf32_to_keysym(c as u32)
+    };
+
+    // Keycodes above 255 can't be used by X11 clients
+    let keycodes = keymap.min_keycode().raw()..=keymap.max_keycode().raw().min(255);
+    let layouts = std::iter::once(active_layout)
+        .chain((0..keymap.num_layouts()).filter(|layout| *layout != active_layout));
+
+    for layout in layouts {
+        let max_level = keycodes
+            .clone()
+            .map(|keycode| keymap.num_levels_for_key(Keycode::new(keycode), layout))
+            .max()
+            .unwrap_or(0);
+
+        for level in 0..max_level {
+            for keycode in keycodes.clone().map(Keycode::new) {
+                if layout >= keymap.num_layouts_for_key(keycode)
+                    || keymap.key_get_syms_by_level(keycode, layout, level) != [target_keysym]
+                {
+                    continue;
+                }
+
+                let mut masks = [xkb::ModMask::default(); 16];
+                let num_masks = keymap.key_get_mods_for_level(keycode, layout, level, &mut masks);
+
+                if let Some(mods) = masks[..num_masks]
+                    .iter()
+                    .filter(|mods| modifier_keycodes(keymap, **mods).is_some())
+                    .min_by_key(|mods| mods.count_ones())
+                {
+                    return Some(CharKey {
+                        keycode,
+                        mods: *mods,
+                        layout,
+                    });
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Modifier keys to hold for `mods` to be depressed, `None` if the keymap can't produce them
+pub fn modifier_keycodes(keymap: &xkb::Keymap, mods: xkb::ModMask) -> Option<Vec<Keycode>> {
+    if mods == 0 {
+        return Some(Vec::new());
+    }
+
+    let mut scratch_state = xkb::State::new(keymap);
+
+    let mut modifier_keycodes = Vec::new();
+    for mod_index in (0..keymap.num_mods()).filter(|index| mods & (1 << index) != 0) {
+        let keycode = (keymap.min_keycode().raw()..=keymap.max_keycode().raw())
+            .map(Keycode::new)
+            .find(|keycode| {
+                scratch_state.update_key(*keycode, KeyDirection::Down);
+                let depressed = scratch_state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
+                scratch_state.update_key(*keycode, KeyDirection::Up);
+
+                depressed == 1 << mod_index
+            })?;
+
+        modifier_keycodes.push(keycode);
+    }
+
+    Some(modifier_keycodes)
+}
+
+/// Binds characters unreachable with `keymap` to its unused keycodes, for as many as there are unused keycodes.
+/// Returns the compiled keymap along with its text, `None` if every character is already reachable
+pub fn with_chars(keymap: &xkb::Keymap, chars: &[char]) -> Result<Option<(xkb::Keymap, String)>> {
+    let mut missing = Vec::new();
+    for ch in chars {
+        let keysym = xkb::utf32_to_keysym(*ch as u32);
+        if keysym != xkb::Keysym::NoSymbol
+            && !missing.contains(&keysym)
+            && char_key(keymap, 0, *ch).is_none()
+        {
+            missing.push(keysym);
+        }
+    }
+
+    if missing.is_empty() {
+        return Ok(None);
+    }
+
+    // Keycodes above 255 can't be used by X11 clients
+    let unused_keycodes = (keymap.min_keycode().raw()..=keymap.max_keycode().raw().min(255))
+        .map(Keycode::new)
+        .filter(|keycode| keymap.num_layouts_for_key(*keycode) == 0);
+
+    let mut keycodes = String::new();
+    let mut symbols = String::new();
+    for (keycode, keysym) in unused_keycodes.zip(missing) {
+        let name = match keymap.key_get_name(keycode) {
+            Some(name) => name.to_string(),
+            None => {
+                let name = format!("W{:03}", keycode.raw());
+                keycodes.push_str(&format!("\t<{name}> = {};\n", keycode.raw()));
+                name
+            }
+        };
+
+        symbols.push_str(&format!(
+            "\tkey <{name}> {{ [ {} ] }};\n",
+            xkb::keysym_get_name(keysym)
+        ));
+    }
+
+    let mut text = keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
+    insert_into_section(&mut text, "xkb_keycodes", &keycodes)?;
+    insert_into_section(&mut text, "xkb_symbols", &symbols)?;
+
+    let xkb_context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    let keymap = xkb::Keymap::new_from_string(
+        &xkb_context,
+        text.clone(),
+        xkb::KEYMAP_FORMAT_TEXT_V1,
+        KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .ok_or(Error::InvalidKeymap)?;
+
+    Ok(Some((keymap, text)))
+}
+
+/// Inserts `lines` at the end of the keymap section starting with `section`
+fn insert_into_section(keymap: &mut String, section: &str, lines: &str) -> Result<()> {
+    let end = keymap
+        .find(section)
+        .and_then(|start| keymap[start..].find("\n};").map(|end| start + end + 1))
+        .ok_or(Error::InvalidKeymap)?;
+
+    keymap.insert_str(end, lines);
+    Ok(())
+}
@@ -1,5 +1,9 @@
 #[cfg(feature = "portals")]
+pub mod eis;
+mod keymap;
+#[cfg(feature = "portals")]
 pub mod portal;
+pub mod recording;
 pub mod traits;
 pub mod wayland;
 
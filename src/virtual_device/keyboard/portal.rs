@@ -1,6 +1,6 @@
 use super::traits::VirtualKeyboard;
-use crate::{KeymapInfo, portal::remote_desktop::RemoteDesktop};
-use xkbcommon::xkb::{self, KEYMAP_COMPILE_NO_FLAGS, KeyDirection, Keycode};
+use crate::{KeymapInfo, Result, portal::remote_desktop::RemoteDesktop};
+use xkbcommon::xkb::{self, KeyDirection, Keycode};
 
 pub struct PortalKeyboard {
     xkb_state: xkb::State,
@@ -8,21 +8,8 @@ pub struct PortalKeyboard {
 }
 
 impl PortalKeyboard {
-    pub fn try_new(
-        remote_desktop: RemoteDesktop,
-        keymap_info: &KeymapInfo,
-    ) -> anyhow::Result<Self> {
-        let xkb_context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
-        let xkb_keymap = unsafe {
-            xkb::Keymap::new_from_fd(
-                &xkb_context,
-                keymap_info.fd.try_clone().unwrap(),
-                keymap_info.size as usize,
-                keymap_info.format.into(),
-                KEYMAP_COMPILE_NO_FLAGS,
-            )?
-        };
-        let xkb_state = xkb::State::new(xkb_keymap.as_ref().unwrap());
+    pub fn try_new(remote_desktop: RemoteDesktop, keymap_info: &KeymapInfo) -> Result<Self> {
+        let xkb_state = xkb::State::new(&keymap_info.compile()?);
 
         Ok(Self {
             xkb_state,
@@ -36,7 +23,7 @@ impl VirtualKeyboard for PortalKeyboard {
         &mut self.xkb_state
     }
 
-    fn key(&mut self, key: Keycode, state: KeyDirection) {
+    fn key(&mut self, key: Keycode, state: KeyDirection) -> Result<()> {
         // xkbcommon doesn't implement Copy for KeyDirection
         #[allow(clippy::needless_match)]
         let state_2 = match state {
@@ -46,8 +33,6 @@ impl VirtualKeyboard for PortalKeyboard {
 
         self.xkb_state.update_key(key, state);
 
-        self.remote_desktop
-            .notify_keyboard_keycode(key, &state_2)
-            .unwrap();
+        self.remote_desktop.notify_keyboard_keycode(key, &state_2)
     }
 }
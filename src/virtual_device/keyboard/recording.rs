@@ -0,0 +1,77 @@
+use super::{keymap, traits::VirtualKeyboard};
+use crate::{
+    Result,
+    recorder::{Event, Recorder},
+};
+use xkbcommon::xkb::{self, KeyDirection, Keycode};
+
+pub struct RecordingKeyboard {
+    xkb_state: xkb::State,
+    xkb_keymap: xkb::Keymap,
+    recorder: Recorder,
+    mapped: bool,
+}
+
+impl RecordingKeyboard {
+    pub fn new(xkb_keymap: &xkb::Keymap, recorder: Recorder) -> Self {
+        Self {
+            xkb_state: xkb::State::new(xkb_keymap),
+            xkb_keymap: xkb_keymap.clone(),
+            recorder,
+            mapped: false,
+        }
+    }
+}
+
+impl VirtualKeyboard for RecordingKeyboard {
+    fn xkb_state(&mut self) -> &mut xkb::State {
+        &mut self.xkb_state
+    }
+
+    fn key(&mut self, key: Keycode, state: KeyDirection) -> Result<()> {
+        let pressed = matches!(state, KeyDirection::Down);
+        self.xkb_state.update_key(key, state);
+
+        self.recorder.record(Event::Key {
+            keycode: key.raw() - 8,
+            pressed,
+        });
+
+        Ok(())
+    }
+
+    fn lock_layout(&mut self, layout: xkb::LayoutIndex) -> bool {
+        let depressed = self.xkb_state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
+        let latched = self.xkb_state.serialize_mods(xkb::STATE_MODS_LATCHED);
+        let locked = self.xkb_state.serialize_mods(xkb::STATE_MODS_LOCKED);
+
+        self.xkb_state
+            .update_mask(depressed, latched, locked, 0, 0, layout);
+        self.recorder.record(Event::Layout(layout));
+
+        true
+    }
+
+    fn map_chars(&mut self, chars: &[char]) -> Result<()> {
+        let Some((xkb_keymap, _)) = keymap::with_chars(&self.xkb_keymap, chars)? else {
+            return Ok(());
+        };
+
+        self.xkb_state = xkb::State::new(&xkb_keymap);
+        self.mapped = true;
+        self.recorder.record(Event::Keymap);
+
+        Ok(())
+    }
+
+    fn restore_keymap(&mut self) -> Result<()> {
+        if !std::mem::take(&mut self.mapped) {
+            return Ok(());
+        }
+
+        self.xkb_state = xkb::State::new(&self.xkb_keymap);
+        self.recorder.record(Event::Keymap);
+
+        Ok(())
+    }
+}
@@ -1,33 +1,90 @@
-use xkbcommon::xkb::{self, KeyDirection, Keycode, keysyms::KEY_Shift_L};
+use super::keymap;
+use crate::{
+    Error, Result,
+    key::{CharKey, Key},
+};
+use std::time::Duration;
+use xkbcommon::xkb::{self, KeyDirection, Keycode};
 
 pub trait VirtualKeyboard {
     fn xkb_state(&mut self) -> &mut xkb::State;
 
-    // https://lists.x.org/archives/wayland-devel/2021-December/042056.html
-    fn keycode_from_char(&mut self, c: char) -> Option<(Keycode, bool)> {
-        let xkb_state = self.xkb_state();
-        let target_keysym = xkb::utf32_to_keysym(c as u32);
+    /// Finds the key, modifiers and layout producing `c`, preferring the active layout and the lowest shift level
+    fn keycode_from_char(&mut self, c: char) -> Option<CharKey> {
+        let active_layout = self
+            .xkb_state()
+            .serialize_layout(xkb::STATE_LAYOUT_EFFECTIVE);
+        keymap::char_key(&self.xkb_state().get_keymap(), active_layout, c)
+    }
+
+    /// Modifier keys to hold for `mods` to be depressed, `None` if the keymap can't produce them
+    fn modifier_keycodes(&mut self, mods: xkb::ModMask) -> Option<Vec<Keycode>> {
+        keymap::modifier_keycodes(&self.xkb_state().get_keymap(), mods)
+    }
+
+    /// Types a single key with the modifiers and layout it needs, returns false without typing anything
+    /// if the layout can't be switched to or the modifiers can't be pressed
+    fn type_char_key(&mut self, char_key: &CharKey, key_hold: Duration) -> Result<bool> {
+        // Without its modifiers the key would type another character
+        let Some(modifiers) = self.modifier_keycodes(char_key.mods) else {
+            return Ok(false);
+        };
 
-        let current_mods = xkb_state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
+        let active_layout = self
+            .xkb_state()
+            .serialize_layout(xkb::STATE_LAYOUT_EFFECTIVE);
+        if char_key.layout != active_layout && !self.lock_layout(char_key.layout) {
+            return Ok(false);
+        }
 
-        for keycode in 8..=255 {
-            let xkb_keycode = xkb::Keycode::new(keycode);
+        for modifier in &modifiers {
+            self.key(*modifier, KeyDirection::Down)?;
+        }
 
-            xkb_state.update_mask(0, 0, 0, 0, 0, 0);
-            if xkb_state.key_get_one_sym(xkb_keycode) == target_keysym {
-                xkb_state.update_mask(current_mods, 0, 0, 0, 0, 0);
-                return Some((xkb_keycode, false));
-            }
+        self.key(char_key.keycode, KeyDirection::Down)?;
+        std::thread::sleep(key_hold);
+        self.key(char_key.keycode, KeyDirection::Up)?;
 
-            xkb_state.update_mask(0, KEY_Shift_L, 0, 0, 0, 0);
-            if xkb_state.key_get_one_sym(xkb_keycode) == target_keysym {
-                xkb_state.update_mask(current_mods, 0, 0, 0, 0, 0);
-                return Some((xkb_keycode, true));
-            }
+        for modifier in modifiers.iter().rev() {
+            self.key(*modifier, KeyDirection::Up)?;
         }
 
-        xkb_state.update_mask(current_mods, 0, 0, 0, 0, 0);
-        None
+        if char_key.layout != active_layout {
+            self.lock_layout(active_layout);
+        }
+
+        Ok(true)
+    }
+
+    fn keycode_from_key(&mut self, key: &Key) -> Option<Keycode> {
+        key.resolve(&self.xkb_state().get_keymap())
+    }
+
+    /// # Errors
+    ///
+    /// One of the keys isn't in the keymap
+    fn keycodes_from_keys(&mut self, keys: &[Key]) -> Result<Vec<Keycode>> {
+        keys.iter()
+            .map(|key| {
+                self.keycode_from_key(key)
+                    .ok_or_else(|| Error::UnknownKey(key.clone()))
+            })
+            .collect()
+    }
+
+    /// Locks the keyboard to `layout`, returns false if the backend can't switch layouts
+    fn lock_layout(&mut self, _layout: xkb::LayoutIndex) -> bool {
+        false
+    }
+
+    /// Binds characters unreachable with the current keymap to unused keycodes until
+    /// [`Self::restore_keymap`] is called. Backends that can't replace the keymap leave them unreachable
+    fn map_chars(&mut self, _chars: &[char]) -> Result<()> {
+        Ok(())
+    }
+
+    fn restore_keymap(&mut self) -> Result<()> {
+        Ok(())
     }
 
     fn is_ctrl_active(&mut self) -> bool {
@@ -36,5 +93,5 @@ pub trait VirtualKeyboard {
         xkb_state.mod_index_is_active(ctrl_mod_index, xkb::STATE_MODS_DEPRESSED)
     }
 
-    fn key(&mut self, key: Keycode, state: KeyDirection);
+    fn key(&mut self, key: Keycode, state: KeyDirection) -> Result<()>;
 }
@@ -1,16 +1,30 @@
-use super::traits::VirtualKeyboard;
-use crate::{KeymapInfo, State};
-use std::os::fd::AsFd;
-use wayland_client::{QueueHandle, globals::GlobalList, protocol::wl_seat};
+use super::{keymap, traits::VirtualKeyboard};
+use crate::{Error, KeymapInfo, Result, State};
+use std::{
+    fs,
+    io::Write,
+    os::fd::{AsFd, OwnedFd},
+};
+use wayland_client::{
+    QueueHandle,
+    globals::GlobalList,
+    protocol::{wl_keyboard, wl_seat},
+};
 use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::{
     zwp_virtual_keyboard_manager_v1, zwp_virtual_keyboard_v1,
 };
 use xkbcommon::xkb::Keycode;
-use xkbcommon::xkb::{self, KEYMAP_COMPILE_NO_FLAGS, KeyDirection};
+use xkbcommon::xkb::{self, KeyDirection};
 
 pub struct WaylandKeyboard {
     virtual_keyboard: zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1,
     xkb_state: xkb::State,
+    xkb_keymap: xkb::Keymap,
+    keymap_format: wl_keyboard::KeymapFormat,
+    keymap_fd: OwnedFd,
+    keymap_size: u32,
+    // Keymap with extra characters bound, kept open until it's replaced
+    mapped_keymap: Option<fs::File>,
 }
 
 impl WaylandKeyboard {
@@ -19,7 +33,7 @@ impl WaylandKeyboard {
         qh: &QueueHandle<State>,
         seat: &wl_seat::WlSeat,
         keymap_info: &KeymapInfo,
-    ) -> anyhow::Result<Self> {
+    ) -> Result<Self> {
         let virtual_keyboard = globals
             .bind::<zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1, _, _>(
                 qh,
@@ -27,19 +41,10 @@ impl WaylandKeyboard {
                 (),
             )
             .map(|virtual_keyboard| virtual_keyboard.create_virtual_keyboard(seat, qh, ()))
-            .map_err(|_| anyhow::anyhow!("Compositor does not support Virtual Keyboard protocol, compile whydotool with `portals` feature"))?;
-
-        let xkb_context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
-        let xkb_keymap = unsafe {
-            xkb::Keymap::new_from_fd(
-                &xkb_context,
-                keymap_info.fd.try_clone().unwrap(),
-                keymap_info.size as usize,
-                keymap_info.format.into(),
-                KEYMAP_COMPILE_NO_FLAGS,
-            )?
-        };
-        let xkb_state = xkb::State::new(xkb_keymap.as_ref().unwrap());
+            .map_err(|_| Error::ProtocolUnsupported("Virtual Keyboard"))?;
+
+        let xkb_keymap = keymap_info.compile()?;
+        let xkb_state = xkb::State::new(&xkb_keymap);
 
         virtual_keyboard.keymap(
             keymap_info.format.into(),
@@ -50,6 +55,11 @@ impl WaylandKeyboard {
         Ok(Self {
             virtual_keyboard,
             xkb_state,
+            xkb_keymap,
+            keymap_format: keymap_info.format,
+            keymap_fd: keymap_info.fd.try_clone()?,
+            keymap_size: keymap_info.size,
+            mapped_keymap: None,
         })
     }
 }
@@ -59,7 +69,7 @@ impl VirtualKeyboard for WaylandKeyboard {
         &mut self.xkb_state
     }
 
-    fn key(&mut self, key: Keycode, state: KeyDirection) {
+    fn key(&mut self, key: Keycode, state: KeyDirection) -> Result<()> {
         let raw_state = match state {
             KeyDirection::Down => 1,
             KeyDirection::Up => 0,
@@ -75,5 +85,57 @@ impl VirtualKeyboard for WaylandKeyboard {
         self.virtual_keyboard.key(0, key.raw() - 8, raw_state);
         self.virtual_keyboard
             .modifiers(depressed, latched, locked, group);
+
+        Ok(())
+    }
+
+    fn lock_layout(&mut self, layout: xkb::LayoutIndex) -> bool {
+        let depressed = self.xkb_state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
+        let latched = self.xkb_state.serialize_mods(xkb::STATE_MODS_LATCHED);
+        let locked = self.xkb_state.serialize_mods(xkb::STATE_MODS_LOCKED);
+
+        self.xkb_state
+            .update_mask(depressed, latched, locked, 0, 0, layout);
+        self.virtual_keyboard
+            .modifiers(depressed, latched, locked, layout);
+
+        true
+    }
+
+    fn map_chars(&mut self, chars: &[char]) -> Result<()> {
+        // Mapped characters are always bound on top of the original keymap
+        let Some((xkb_keymap, keymap)) = keymap::with_chars(&self.xkb_keymap, chars)? else {
+            return Ok(());
+        };
+
+        let mut file = tempfile::tempfile()?;
+        file.write_all(keymap.as_bytes())?;
+        file.write_all(b"\0")?;
+
+        self.virtual_keyboard.keymap(
+            wl_keyboard::KeymapFormat::XkbV1.into(),
+            file.as_fd(),
+            keymap.len() as u32 + 1,
+        );
+
+        self.xkb_state = xkb::State::new(&xkb_keymap);
+        self.mapped_keymap = Some(file);
+
+        Ok(())
+    }
+
+    fn restore_keymap(&mut self) -> Result<()> {
+        if self.mapped_keymap.take().is_none() {
+            return Ok(());
+        }
+
+        self.virtual_keyboard.keymap(
+            self.keymap_format.into(),
+            self.keymap_fd.as_fd(),
+            self.keymap_size,
+        );
+        self.xkb_state = xkb::State::new(&self.xkb_keymap);
+
+        Ok(())
     }
 }
@@ -1,2 +1,3 @@
 pub mod keyboard;
 pub mod pointer;
+pub mod touch;
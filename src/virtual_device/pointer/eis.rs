@@ -0,0 +1,105 @@
+use super::traits::VirtualPointer;
+use crate::{
+    Result, ScrollSource,
+    eis::{self, Arg, Eis},
+    output::Outputs,
+    portal::remote_desktop::RemoteDesktop,
+};
+use wayland_client::protocol::wl_pointer;
+
+// Discrete scrolls are in fractions of a wheel click
+const CLICK_FRACTIONS: i32 = 120;
+
+pub struct EisPointer {
+    // Closes the connection before the session it came from
+    eis: Eis,
+    _remote_desktop: RemoteDesktop,
+    outputs: Outputs,
+}
+
+impl EisPointer {
+    pub const fn new(eis: Eis, remote_desktop: RemoteDesktop, outputs: Outputs) -> Self {
+        Self {
+            eis,
+            _remote_desktop: remote_desktop,
+            outputs,
+        }
+    }
+}
+
+impl VirtualPointer for EisPointer {
+    fn button(&self, button: u32, state: wl_pointer::ButtonState) -> Result<()> {
+        let pressed = state == wl_pointer::ButtonState::Pressed;
+        self.eis.request(
+            eis::BUTTON,
+            1,
+            &[Arg::Uint(button), Arg::Uint(u32::from(pressed))],
+        )
+    }
+
+    fn scroll(&self, xpos: f64, ypos: f64) -> Result<()> {
+        self.eis.request(
+            eis::SCROLL,
+            1,
+            &[Arg::Float(xpos as f32), Arg::Float(ypos as f32)],
+        )
+    }
+
+    fn scroll_source(&self, xpos: f64, ypos: f64, source: ScrollSource) -> Result<()> {
+        self.scroll(xpos, ypos)?;
+
+        // EI has no sources, lifting the fingers is a stop on the scrolled axes
+        if source == ScrollSource::Finger {
+            self.eis.request(
+                eis::SCROLL,
+                3,
+                &[
+                    Arg::Uint(u32::from(xpos != 0.0)),
+                    Arg::Uint(u32::from(ypos != 0.0)),
+                    // Stopped rather than cancelled, so clients may scroll on kinetically
+                    Arg::Uint(0),
+                ],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn scroll_discrete(&self, xclicks: i32, yclicks: i32) -> Result<()> {
+        self.eis.request(
+            eis::SCROLL,
+            2,
+            &[
+                Arg::Int(xclicks * CLICK_FRACTIONS),
+                Arg::Int(yclicks * CLICK_FRACTIONS),
+            ],
+        )
+    }
+
+    fn motion(&self, xpos: f64, ypos: f64) -> Result<()> {
+        self.eis.request(
+            eis::POINTER,
+            1,
+            &[Arg::Float(xpos as f32), Arg::Float(ypos as f32)],
+        )
+    }
+
+    fn motion_absolute(&self, xpos: u32, ypos: u32) -> Result<()> {
+        // EI positions are in the compositor's layout
+        let (x, y) = self.outputs.layout_position(xpos, ypos);
+        self.eis.request(
+            eis::POINTER_ABSOLUTE,
+            1,
+            &[Arg::Float(x as f32), Arg::Float(y as f32)],
+        )
+    }
+
+    fn motion_absolute_output(&self, output: &str, xpos: u32, ypos: u32) -> Result<()> {
+        let (x, y) = self.outputs.absolute_position(output, xpos, ypos)?;
+        self.motion_absolute(x, y)
+    }
+
+    fn absolute_size(&self, output: Option<&str>) -> Result<(u32, u32)> {
+        self.outputs.size(output)
+    }
+}
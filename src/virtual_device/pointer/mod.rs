@@ -1,4 +1,7 @@
 #[cfg(feature = "portals")]
+pub mod eis;
+#[cfg(feature = "portals")]
 pub mod portal;
+pub mod recording;
 pub mod traits;
 pub mod wayland;
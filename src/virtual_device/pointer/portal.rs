@@ -1,110 +1,104 @@
 use super::traits::VirtualPointer;
-use crate::portal::remote_desktop::RemoteDesktop;
-use anyhow::Context;
-use pipewire as pw;
-use pw::{context, main_loop, properties::properties, spa, stream::StreamState};
-use std::process;
+use crate::{
+    Result, ScrollSource,
+    output::Outputs,
+    portal::{
+        remote_desktop::RemoteDesktop,
+        stream::{self, Consumer},
+    },
+};
+use std::cell::OnceCell;
 use wayland_client::protocol::wl_pointer;
 
 pub struct PortalPointer {
     remote_desktop: RemoteDesktop,
+    outputs: Outputs,
+    // Streams are only consumed once absolute motion needs them
+    consumer: OnceCell<Consumer>,
 }
 
 impl PortalPointer {
-    pub fn new(remote_desktop: RemoteDesktop) -> Self {
-        Self { remote_desktop }
+    pub const fn new(remote_desktop: RemoteDesktop, outputs: Outputs) -> Self {
+        Self {
+            remote_desktop,
+            outputs,
+            consumer: OnceCell::new(),
+        }
     }
 
-    fn motion_absolute_impl(&self, xpos: u32, ypos: u32, node_id: u32) -> anyhow::Result<()> {
-        pw::init();
-
-        let pw_fd = self
-            .remote_desktop
-            .open_pipewire_remote()
-            .context("Failed to open PipeWire remote")?;
-
-        let mainloop =
-            main_loop::MainLoopRc::new(None).context("Failed to create PipeWire main loop")?;
-
-        let context = context::ContextRc::new(&mainloop, None)
-            .context("Failed to create PipeWire context")?;
-
-        let core = context
-            .connect_fd_rc(pw_fd.into(), None)
-            .context("Failed to connect PipeWire core")?;
-
-        let stream = pw::stream::StreamRc::new(
-            core,
-            "whydotool",
-            properties! {
-                *pipewire::keys::MEDIA_TYPE => "Video",
-                *pipewire::keys::MEDIA_CATEGORY => "Capture",
-                *pipewire::keys::MEDIA_ROLE => "Screen",
-            },
-        )
-        .context("Failed to create PipeWire stream")?;
-
-        let mainloop_ref = mainloop.clone();
-        let _listener = stream
-            .add_local_listener()
-            .state_changed(move |_, _: &mut (), _, new| {
-                if new == StreamState::Streaming {
-                    mainloop_ref.quit();
-                }
-            })
-            .register();
-
-        stream
-            .connect(
-                spa::utils::Direction::Input,
-                Some(node_id),
-                pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
-                &mut [],
-            )
-            .context("Failed to connect PipeWire stream")?;
+    fn motion_absolute_impl(&self, xpos: u32, ypos: u32, node_id: u32) -> Result<()> {
+        let consumer = match self.consumer.get() {
+            Some(consumer) => consumer,
+            None => {
+                let consumer = Consumer::connect(&self.remote_desktop)?;
+                self.consumer.get_or_init(|| consumer)
+            }
+        };
+        consumer.dispatch();
 
         self.remote_desktop
             .notify_pointer_motion_absolute(xpos as f32, ypos as f32, node_id)
-            .context("Failed to notify pointer motion absolute")?;
-
-        mainloop.run();
-        Ok(())
     }
 }
 
 impl VirtualPointer for PortalPointer {
-    fn button(&self, button: u32, state: wl_pointer::ButtonState) {
+    fn button(&self, button: u32, state: wl_pointer::ButtonState) -> Result<()> {
         self.remote_desktop
             .notify_pointer_button(button as i32, state)
-            .unwrap();
     }
 
-    fn scroll(&self, xpos: f64, ypos: f64) {
+    fn scroll(&self, xpos: f64, ypos: f64) -> Result<()> {
         self.remote_desktop
-            .notify_pointer_axis(xpos as f32, ypos as f32)
-            .unwrap();
+            .notify_pointer_axis(xpos as f32, ypos as f32, false)
     }
 
-    fn motion(&self, xpos: f64, ypos: f64) {
+    fn scroll_source(&self, xpos: f64, ypos: f64, source: ScrollSource) -> Result<()> {
+        // The portal has no sources, only whether a series of scrolls is finished
+        self.remote_desktop.notify_pointer_axis(
+            xpos as f32,
+            ypos as f32,
+            source == ScrollSource::Finger,
+        )
+    }
+
+    fn scroll_discrete(&self, xclicks: i32, yclicks: i32) -> Result<()> {
+        if yclicks != 0 {
+            self.remote_desktop
+                .notify_pointer_axis_discrete(wl_pointer::Axis::VerticalScroll, yclicks)?;
+        }
+        if xclicks != 0 {
+            self.remote_desktop
+                .notify_pointer_axis_discrete(wl_pointer::Axis::HorizontalScroll, xclicks)?;
+        }
+
+        Ok(())
+    }
+
+    fn motion(&self, xpos: f64, ypos: f64) -> Result<()> {
         self.remote_desktop
             .notify_pointer_motion(xpos as f32, ypos as f32)
-            .unwrap();
     }
 
-    fn motion_absolute(&self, xpos: u32, ypos: u32) {
-        if let Some(node_id) = self
-            .remote_desktop
-            .streams()
-            .as_ref()
-            .and_then(|streams| streams.first().map(|stream| stream.0))
-        {
-            if let Err(e) = self.motion_absolute_impl(xpos, ypos, node_id) {
-                eprintln!("motion_absolute failed: {e:#}");
-                process::exit(1);
+    fn motion_absolute(&self, xpos: u32, ypos: u32) -> Result<()> {
+        // Positions span every output, but the portal takes them within the stream of one
+        let (x, y) = self.outputs.layout_position(xpos, ypos);
+        let (node_id, (x, y)) = stream::at(&self.remote_desktop, x, y)?;
+        self.motion_absolute_impl(x as u32, y as u32, node_id)
+    }
+
+    fn motion_absolute_output(&self, output: &str, xpos: u32, ypos: u32) -> Result<()> {
+        // Positions within a stream are relative to it already
+        let (node_id, _) = stream::find(&self.remote_desktop, &self.outputs, Some(output))?;
+        self.motion_absolute_impl(xpos, ypos, *node_id)
+    }
+
+    fn absolute_size(&self, output: Option<&str>) -> Result<(u32, u32)> {
+        match output {
+            Some(_) => {
+                let (_, properties) = stream::find(&self.remote_desktop, &self.outputs, output)?;
+                stream::size(properties)
             }
-        } else {
-            eprintln!("No PipeWire node found for pointer motion_absolute");
-            process::exit(1);
+            None => self.outputs.size(None),
         }
     }
 }
@@ -0,0 +1,46 @@
+use super::traits::VirtualPointer;
+use crate::{
+    Result,
+    recorder::{Event, Recorder},
+};
+use wayland_client::protocol::wl_pointer;
+
+pub struct RecordingPointer {
+    recorder: Recorder,
+}
+
+impl RecordingPointer {
+    pub const fn new(recorder: Recorder) -> Self {
+        Self { recorder }
+    }
+}
+
+impl VirtualPointer for RecordingPointer {
+    fn button(&self, button: u32, state: wl_pointer::ButtonState) -> Result<()> {
+        self.recorder.record(Event::Button {
+            button,
+            pressed: state == wl_pointer::ButtonState::Pressed,
+        });
+
+        Ok(())
+    }
+
+    fn scroll(&self, xpos: f64, ypos: f64) -> Result<()> {
+        self.recorder.record(Event::Scroll { x: xpos, y: ypos });
+
+        Ok(())
+    }
+
+    fn motion(&self, xpos: f64, ypos: f64) -> Result<()> {
+        self.recorder.record(Event::Motion { x: xpos, y: ypos });
+
+        Ok(())
+    }
+
+    fn motion_absolute(&self, xpos: u32, ypos: u32) -> Result<()> {
+        self.recorder
+            .record(Event::MotionAbsolute { x: xpos, y: ypos });
+
+        Ok(())
+    }
+}
@@ -1,11 +1,58 @@
+use crate::{CLICK_DISTANCE, Error, Result, ScrollSource};
 use wayland_client::protocol::wl_pointer;
 
 pub trait VirtualPointer {
-    fn button(&self, button: u32, state: wl_pointer::ButtonState);
+    fn button(&self, button: u32, state: wl_pointer::ButtonState) -> Result<()>;
 
-    fn scroll(&self, xpos: f64, ypos: f64);
+    fn scroll(&self, xpos: f64, ypos: f64) -> Result<()>;
 
-    fn motion(&self, xpos: f64, ypos: f64);
+    /// Scrolls as if from `source`, finger scrolls end as if the fingers were lifted
+    ///
+    /// # Errors
+    ///
+    /// Sending the scroll failed
+    fn scroll_source(&self, xpos: f64, ypos: f64, _source: ScrollSource) -> Result<()> {
+        self.scroll(xpos, ypos)
+    }
 
-    fn motion_absolute(&self, xpos: u32, ypos: u32);
+    /// Scrolls by whole wheel clicks, for clients that ignore anything else
+    ///
+    /// # Errors
+    ///
+    /// Sending the scroll failed
+    fn scroll_discrete(&self, xclicks: i32, yclicks: i32) -> Result<()> {
+        self.scroll(
+            f64::from(xclicks) * CLICK_DISTANCE,
+            f64::from(yclicks) * CLICK_DISTANCE,
+        )
+    }
+
+    fn motion(&self, xpos: f64, ypos: f64) -> Result<()>;
+
+    /// Moves to a position relative to the top left corner of the smallest rectangle containing every output
+    ///
+    /// # Errors
+    ///
+    /// Sending the motion failed, or no shared stream is at the position
+    fn motion_absolute(&self, xpos: u32, ypos: u32) -> Result<()>;
+
+    /// Moves to a position relative to the top left corner of the output named `output`
+    ///
+    /// # Errors
+    ///
+    /// No output is named `output`
+    fn motion_absolute_output(&self, output: &str, _xpos: u32, _ypos: u32) -> Result<()> {
+        Err(Error::UnknownOutput(output.into()))
+    }
+
+    /// Width and height of the space absolute positions are in, the output named `output` or everything
+    ///
+    /// # Errors
+    ///
+    /// No output is named `output`, or the size is unknown
+    fn absolute_size(&self, output: Option<&str>) -> Result<(u32, u32)> {
+        Err(output.map_or(Error::UnknownSize, |output| {
+            Error::UnknownOutput(output.into())
+        }))
+    }
 }
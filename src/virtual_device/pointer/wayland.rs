@@ -1,7 +1,7 @@
 use super::traits::VirtualPointer;
-use crate::{State, output::Outputs};
+use crate::{CLICK_DISTANCE, Error, Result, ScrollSource, State, output::Outputs};
 use wayland_client::{
-    QueueHandle,
+    Proxy, QueueHandle,
     globals::GlobalList,
     protocol::{wl_pointer, wl_seat},
 };
@@ -20,7 +20,7 @@ impl WaylandPointer {
         qh: &QueueHandle<State>,
         seat: Option<&wl_seat::WlSeat>,
         outputs: Outputs,
-    ) -> anyhow::Result<Self> {
+    ) -> Result<Self> {
         let virtual_pointer = globals
             .bind::<zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1, _, _>(
                 qh,
@@ -30,7 +30,7 @@ impl WaylandPointer {
             .map(|virtual_pointer_manager| {
                 virtual_pointer_manager.create_virtual_pointer(seat, qh, ())
             })
-            .map_err(|_| anyhow::anyhow!("Compositor does not support Virtual Pointer protocol, compile whydotool with `portals` feature"))?;
+            .map_err(|_| Error::ProtocolUnsupported("Virtual Pointer"))?;
 
         Ok(Self {
             virtual_pointer,
@@ -40,29 +40,99 @@ impl WaylandPointer {
 }
 
 impl VirtualPointer for WaylandPointer {
-    fn button(&self, button: u32, state: wl_pointer::ButtonState) {
+    fn button(&self, button: u32, state: wl_pointer::ButtonState) -> Result<()> {
         self.virtual_pointer.button(0, button, state);
         self.virtual_pointer.frame();
+
+        Ok(())
     }
 
-    fn scroll(&self, xpos: f64, ypos: f64) {
-        self.virtual_pointer
-            .axis(0, wl_pointer::Axis::VerticalScroll, ypos);
+    fn scroll(&self, xpos: f64, ypos: f64) -> Result<()> {
+        for (axis, value) in axes(xpos, ypos) {
+            self.virtual_pointer.axis(0, axis, value);
+        }
+        self.virtual_pointer.frame();
+
+        Ok(())
+    }
+
+    fn scroll_source(&self, xpos: f64, ypos: f64, source: ScrollSource) -> Result<()> {
+        // Sources and stops came with version 2, older pointers only scroll
+        let sourced = self.virtual_pointer.version() >= 2;
+        if sourced {
+            self.virtual_pointer.axis_source(match source {
+                ScrollSource::Wheel => wl_pointer::AxisSource::Wheel,
+                ScrollSource::Finger => wl_pointer::AxisSource::Finger,
+                ScrollSource::Continuous => wl_pointer::AxisSource::Continuous,
+            });
+        }
+        self.scroll(xpos, ypos)?;
+
+        // Lifted fingers stop the scroll in a frame of its own, which is where kinetic scrolling starts
+        if sourced && source == ScrollSource::Finger {
+            self.virtual_pointer
+                .axis_source(wl_pointer::AxisSource::Finger);
+            for (axis, _) in axes(xpos, ypos) {
+                self.virtual_pointer.axis_stop(0, axis);
+            }
+            self.virtual_pointer.frame();
+        }
+
+        Ok(())
+    }
+
+    fn scroll_discrete(&self, xclicks: i32, yclicks: i32) -> Result<()> {
+        if self.virtual_pointer.version() < 2 {
+            return self.scroll(
+                f64::from(xclicks) * CLICK_DISTANCE,
+                f64::from(yclicks) * CLICK_DISTANCE,
+            );
+        }
+
         self.virtual_pointer
-            .axis(0, wl_pointer::Axis::HorizontalScroll, xpos);
+            .axis_source(wl_pointer::AxisSource::Wheel);
+        for (axis, value) in axes(f64::from(xclicks), f64::from(yclicks)) {
+            self.virtual_pointer
+                .axis_discrete(0, axis, value * CLICK_DISTANCE, value as i32);
+        }
         self.virtual_pointer.frame();
+
+        Ok(())
     }
 
-    fn motion(&self, xpos: f64, ypos: f64) {
+    fn motion(&self, xpos: f64, ypos: f64) -> Result<()> {
         self.virtual_pointer.motion(0, xpos, ypos);
         self.virtual_pointer.frame();
+
+        Ok(())
     }
 
-    fn motion_absolute(&self, xpos: u32, ypos: u32) {
+    fn motion_absolute(&self, xpos: u32, ypos: u32) -> Result<()> {
         let (width, height) = self.outputs.dimensions();
 
         self.virtual_pointer
             .motion_absolute(0, xpos, ypos, width as u32, height as u32);
         self.virtual_pointer.frame();
+
+        Ok(())
+    }
+
+    fn motion_absolute_output(&self, output: &str, xpos: u32, ypos: u32) -> Result<()> {
+        let (x, y) = self.outputs.absolute_position(output, xpos, ypos)?;
+        self.motion_absolute(x, y)
+    }
+
+    fn absolute_size(&self, output: Option<&str>) -> Result<(u32, u32)> {
+        self.outputs.size(output)
     }
 }
+
+/// Axes that are scrolled along, with how far
+fn axes(xpos: f64, ypos: f64) -> impl Iterator<Item = (wl_pointer::Axis, f64)> {
+    [
+        (wl_pointer::Axis::VerticalScroll, ypos),
+        (wl_pointer::Axis::HorizontalScroll, xpos),
+    ]
+    .into_iter()
+    .filter(|(_, value)| *value != 0.0)
+}
@@ -0,0 +1,78 @@
+use super::traits::VirtualTouch;
+use crate::{
+    Error, Result,
+    eis::{self, Arg, Eis},
+    output::{Output, Outputs},
+    portal::remote_desktop::RemoteDesktop,
+};
+
+pub struct EisTouch {
+    // Closes the connection before the session it came from
+    eis: Eis,
+    _remote_desktop: RemoteDesktop,
+    outputs: Outputs,
+}
+
+impl EisTouch {
+    pub const fn new(eis: Eis, remote_desktop: RemoteDesktop, outputs: Outputs) -> Self {
+        Self {
+            eis,
+            _remote_desktop: remote_desktop,
+            outputs,
+        }
+    }
+
+    fn output(&self, output: Option<&str>) -> Result<Output> {
+        match output {
+            Some(output) => self
+                .outputs
+                .find(output)
+                .ok_or_else(|| Error::UnknownOutput(output.into())),
+            None => self
+                .outputs
+                .to_vec()
+                .into_iter()
+                .next()
+                .ok_or(Error::UnknownSize),
+        }
+    }
+
+    /// Touches within `output`, EI positions are in the compositor's logical layout
+    fn touch(
+        &self,
+        opcode: u32,
+        output: Option<&str>,
+        slot: u32,
+        xpos: f64,
+        ypos: f64,
+    ) -> Result<()> {
+        let output = self.output(output)?;
+        let x = f64::from(output.x) + xpos;
+        let y = f64::from(output.y) + ypos;
+
+        self.eis.request(
+            eis::TOUCHSCREEN,
+            opcode,
+            &[Arg::Uint(slot), Arg::Float(x as f32), Arg::Float(y as f32)],
+        )
+    }
+}
+
+impl VirtualTouch for EisTouch {
+    fn down(&self, output: Option<&str>, slot: u32, xpos: f64, ypos: f64) -> Result<()> {
+        self.touch(1, output, slot, xpos, ypos)
+    }
+
+    fn motion(&self, output: Option<&str>, slot: u32, xpos: f64, ypos: f64) -> Result<()> {
+        self.touch(2, output, slot, xpos, ypos)
+    }
+
+    fn up(&self, slot: u32) -> Result<()> {
+        self.eis.request(eis::TOUCHSCREEN, 3, &[Arg::Uint(slot)])
+    }
+
+    fn size(&self, output: Option<&str>) -> Result<(u32, u32)> {
+        let output = self.output(output)?;
+        Ok((output.width as u32, output.height as u32))
+    }
+}
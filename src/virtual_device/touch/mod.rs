@@ -0,0 +1,5 @@
+#[cfg(feature = "portals")]
+pub mod eis;
+#[cfg(feature = "portals")]
+pub mod portal;
+pub mod traits;
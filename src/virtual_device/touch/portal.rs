@@ -0,0 +1,55 @@
+use super::traits::VirtualTouch;
+use crate::{
+    Result,
+    output::Outputs,
+    portal::{
+        remote_desktop::RemoteDesktop,
+        stream::{self, Consumer},
+    },
+};
+
+pub struct PortalTouch {
+    remote_desktop: RemoteDesktop,
+    outputs: Outputs,
+    consumer: Consumer,
+}
+
+impl PortalTouch {
+    /// # Errors
+    ///
+    /// The session's streams couldn't be consumed
+    pub fn try_new(remote_desktop: RemoteDesktop, outputs: Outputs) -> Result<Self> {
+        let consumer = Consumer::connect(&remote_desktop)?;
+
+        Ok(Self {
+            remote_desktop,
+            outputs,
+            consumer,
+        })
+    }
+}
+
+impl VirtualTouch for PortalTouch {
+    fn down(&self, output: Option<&str>, slot: u32, xpos: f64, ypos: f64) -> Result<()> {
+        let (node_id, _) = stream::find(&self.remote_desktop, &self.outputs, output)?;
+        self.consumer.dispatch();
+        self.remote_desktop
+            .notify_touch_down(*node_id, slot, xpos, ypos)
+    }
+
+    fn motion(&self, output: Option<&str>, slot: u32, xpos: f64, ypos: f64) -> Result<()> {
+        let (node_id, _) = stream::find(&self.remote_desktop, &self.outputs, output)?;
+        self.consumer.dispatch();
+        self.remote_desktop
+            .notify_touch_motion(*node_id, slot, xpos, ypos)
+    }
+
+    fn up(&self, slot: u32) -> Result<()> {
+        self.remote_desktop.notify_touch_up(slot)
+    }
+
+    fn size(&self, output: Option<&str>) -> Result<(u32, u32)> {
+        let (_, properties) = stream::find(&self.remote_desktop, &self.outputs, output)?;
+        stream::size(properties)
+    }
+}
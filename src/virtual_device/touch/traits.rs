@@ -0,0 +1,33 @@
+use crate::Result;
+
+/// Touchscreen, on which fingers touching at once are told apart by their slot.
+/// Positions are relative to the output named `output`, or the first one if it's `None`
+pub trait VirtualTouch {
+    /// Puts the finger in `slot` down
+    ///
+    /// # Errors
+    ///
+    /// No output is named `output`, or sending the touch failed
+    fn down(&self, output: Option<&str>, slot: u32, xpos: f64, ypos: f64) -> Result<()>;
+
+    /// Moves the finger in `slot`, which is down
+    ///
+    /// # Errors
+    ///
+    /// No output is named `output`, or sending the touch failed
+    fn motion(&self, output: Option<&str>, slot: u32, xpos: f64, ypos: f64) -> Result<()>;
+
+    /// Lifts the finger in `slot`
+    ///
+    /// # Errors
+    ///
+    /// Sending the touch failed
+    fn up(&self, slot: u32) -> Result<()>;
+
+    /// Width and height of the output named `output`
+    ///
+    /// # Errors
+    ///
+    /// No output is named `output`, or the size is unknown
+    fn size(&self, output: Option<&str>) -> Result<(u32, u32)>;
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use common::server::{Request, ServerConfig, TestServer, whydotool_command};
+use libwhydotool::{Click, Event};
+use std::time::{Duration, Instant};
+use wayland_client::protocol::wl_pointer::ButtonState;
+
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+
+#[test]
+fn decode_byte() {
+    assert_eq!(
+        Click::from_byte(0xC0),
+        Click {
+            button: BTN_LEFT,
+            press: true,
+            release: true,
+        }
+    );
+    assert_eq!(
+        Click::from_byte(0x41),
+        Click {
+            button: BTN_RIGHT,
+            press: true,
+            release: false,
+        }
+    );
+    assert_eq!(
+        Click::from_byte(0x81),
+        Click {
+            button: BTN_RIGHT,
+            press: false,
+            release: true,
+        }
+    );
+}
+
+#[test]
+fn parse_hex_and_decimal() {
+    assert_eq!("0xC0".parse::<Click>().unwrap(), Click::from_byte(0xC0));
+    assert_eq!("0Xc1".parse::<Click>().unwrap(), Click::from_byte(0xC1));
+    assert_eq!("192".parse::<Click>().unwrap(), Click::from_byte(0xC0));
+
+    assert!("0x".parse::<Click>().is_err());
+    assert!("0x1C0".parse::<Click>().is_err());
+    assert!("wheel".parse::<Click>().is_err());
+}
+
+#[test]
+fn parse_buttons_and_states() {
+    let click = |button, press, release| Click {
+        button,
+        press,
+        release,
+    };
+
+    assert_eq!(
+        "left".parse::<Click>().unwrap(),
+        click(BTN_LEFT, true, true)
+    );
+    assert_eq!(
+        "Right:1".parse::<Click>().unwrap(),
+        click(BTN_RIGHT, true, false)
+    );
+    assert_eq!(
+        "middle:down".parse::<Click>().unwrap(),
+        click(0x112, true, false)
+    );
+    assert_eq!(
+        "side:up".parse::<Click>().unwrap(),
+        click(0x113, false, true)
+    );
+    assert_eq!("extra".parse::<Click>().unwrap(), click(0x114, true, true));
+    assert_eq!(
+        "BTN_TASK:0".parse::<Click>().unwrap(),
+        click(0x117, false, true)
+    );
+    // Raw codes reach buttons past the byte encoding, like BTN_TRIGGER_HAPPY1
+    assert_eq!("0x2c0".parse::<Click>().unwrap(), click(0x2c0, true, true));
+    assert_eq!(
+        "0x113:down".parse::<Click>().unwrap(),
+        click(0x113, true, false)
+    );
+
+    assert!("left:pressed".parse::<Click>().is_err());
+    // KEY_A isn't a button
+    assert!("30:down".parse::<Click>().is_err());
+}
+
+#[test]
+fn count_clicks_in_a_row() {
+    let (server, socket) = TestServer::start_socket(ServerConfig::default());
+
+    let output = whydotool_command(&socket)
+        .args(["click", "--count", "2", "right", "middle:down", "middle:up"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let buttons: Vec<_> = server
+        .requests()
+        .into_iter()
+        .filter_map(|request| match request {
+            Request::Button { button, pressed } => Some((button, pressed)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        buttons,
+        [
+            (BTN_RIGHT, true),
+            (BTN_RIGHT, false),
+            (BTN_RIGHT, true),
+            (BTN_RIGHT, false),
+            (0x112, true),
+            (0x112, false),
+        ]
+    );
+}
+
+#[test]
+fn next_delay_after_every_click() {
+    let (_server, socket) = TestServer::start_socket(ServerConfig::default());
+
+    let start = Instant::now();
+    let output = whydotool_command(&socket)
+        .args([
+            "click", "-D", "100", "-r", "2", "--count", "2", "left", "0xC1",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert!(start.elapsed() >= Duration::from_millis(600));
+}
+
+#[test]
+fn click_is_recorded() {
+    let (whydotool, recorder) = common::whydotool();
+    let pointer = whydotool.virtual_pointer().unwrap();
+
+    let click = Click::from_byte(0xC0);
+    pointer.button(click.button, ButtonState::Pressed).unwrap();
+    pointer.button(click.button, ButtonState::Released).unwrap();
+
+    assert_eq!(
+        common::events(&recorder),
+        [
+            Event::Button {
+                button: BTN_LEFT,
+                pressed: true,
+            },
+            Event::Button {
+                button: BTN_LEFT,
+                pressed: false,
+            },
+        ]
+    );
+
+    let recorded = recorder.events();
+    assert!(recorded[0].time <= recorded[1].time);
+
+    recorder.clear();
+    assert!(recorder.events().is_empty());
+}
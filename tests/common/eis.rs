@@ -0,0 +1,258 @@
+//! Stand-in for an EIS server behind the portal's `ConnectToEIS`, offering a single seat
+//! with a device for each interface that was bound
+
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+};
+
+const HEADER_SIZE: usize = 16;
+/// Ids of objects created by the server start here, below it they are the client's
+const SERVER_ID: u64 = 0xff00_0000_0000_0000;
+
+/// Interfaces of devices, bound with the seat capability of the same index
+const CAPABILITIES: &[&str] = &[
+    "ei_pointer",
+    "ei_pointer_absolute",
+    "ei_scroll",
+    "ei_button",
+    "ei_keyboard",
+    "ei_touchscreen",
+];
+
+/// Request sent by the client on a device
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    StartEmulating,
+    StopEmulating,
+    Frame,
+    MotionRelative { dx: f32, dy: f32 },
+    MotionAbsolute { x: f32, y: f32 },
+    Button { button: u32, pressed: bool },
+    Scroll { dx: f32, dy: f32 },
+    ScrollDiscrete { dx: i32, dy: i32 },
+    ScrollStop { x: bool, y: bool, cancel: bool },
+    Key { key: u32, pressed: bool },
+    TouchDown { id: u32, x: f32, y: f32 },
+    TouchMotion { id: u32, x: f32, y: f32 },
+    TouchUp { id: u32 },
+}
+
+enum Object {
+    Handshake,
+    Connection,
+    Seat,
+    Device,
+    Interface(&'static str),
+    Pingpong,
+}
+
+struct Server {
+    socket: UnixStream,
+    objects: HashMap<u64, Object>,
+    next_id: u64,
+    serial: u32,
+    seat: bool,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+/// Serves EIS on a new socket until the client closes it, recording device requests in `requests`.
+/// No seat is offered unless `seat` is set. Returns the client's end
+pub fn serve(requests: Arc<Mutex<Vec<Request>>>, seat: bool) -> UnixStream {
+    let (client, socket) = UnixStream::pair().unwrap();
+
+    thread::spawn(move || {
+        let mut server = Server {
+            socket,
+            objects: HashMap::from([(0, Object::Handshake)]),
+            next_id: SERVER_ID,
+            serial: 0,
+            seat,
+            requests,
+        };
+        server.run();
+    });
+
+    client
+}
+
+impl Server {
+    fn run(&mut self) {
+        // Handshake version
+        self.send(0, 0, &[&1u32.to_ne_bytes()]);
+
+        let mut incoming = Vec::new();
+        let mut buffer = [0; 4096];
+        loop {
+            match self.socket.read(&mut buffer) {
+                Ok(0) | Err(_) => return,
+                Ok(read) => incoming.extend(&buffer[..read]),
+            }
+
+            while incoming.len() >= HEADER_SIZE {
+                let length = u32::from_ne_bytes(incoming[8..12].try_into().unwrap()) as usize;
+                if incoming.len() < length {
+                    break;
+                }
+
+                let message: Vec<_> = incoming.drain(..length).collect();
+                let object = u64::from_ne_bytes(message[..8].try_into().unwrap());
+                let opcode = u32::from_ne_bytes(message[12..16].try_into().unwrap());
+                self.handle(object, opcode, &message[HEADER_SIZE..]);
+            }
+        }
+    }
+
+    fn new_id(&mut self, object: Object) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.objects.insert(id, object);
+        id
+    }
+
+    fn serial(&mut self) -> [u8; 4] {
+        self.serial += 1;
+        self.serial.to_ne_bytes()
+    }
+
+    fn send(&mut self, object: u64, opcode: u32, args: &[&[u8]]) {
+        let body = args.concat();
+        let mut message = Vec::new();
+        message.extend(object.to_ne_bytes());
+        message.extend(((HEADER_SIZE + body.len()) as u32).to_ne_bytes());
+        message.extend(opcode.to_ne_bytes());
+        message.extend(body);
+        let _ = self.socket.write_all(&message);
+    }
+
+    fn record(&self, request: Request) {
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(request);
+    }
+
+    fn handle(&mut self, object: u64, opcode: u32, args: &[u8]) {
+        let uint = |index: usize| u32::from_ne_bytes(args[index * 4..][..4].try_into().unwrap());
+        let int = |index: usize| uint(index) as i32;
+        let float = |index: usize| f32::from_bits(uint(index));
+
+        let Some(kind) = self.objects.get(&object) else {
+            return;
+        };
+        match (kind, opcode) {
+            // Handshake finished
+            (Object::Handshake, 1) => {
+                let connection = self.new_id(Object::Connection);
+                let serial = self.serial();
+                self.send(
+                    0,
+                    2,
+                    &[&serial, &connection.to_ne_bytes(), &1u32.to_ne_bytes()],
+                );
+
+                if self.seat {
+                    let seat = self.new_id(Object::Seat);
+                    self.send(connection, 1, &[&seat.to_ne_bytes(), &1u32.to_ne_bytes()]);
+                    for (bit, interface) in CAPABILITIES.iter().enumerate() {
+                        self.send(seat, 2, &[&(1u64 << bit).to_ne_bytes(), &string(interface)]);
+                    }
+                    self.send(seat, 3, &[]);
+                }
+
+                // Pinged once, like servers checking the client is alive
+                let pingpong = self.new_id(Object::Pingpong);
+                self.send(
+                    connection,
+                    3,
+                    &[&pingpong.to_ne_bytes(), &1u32.to_ne_bytes()],
+                );
+            }
+            // Sync, answered right away as everything before was handled
+            (Object::Connection, 0) => {
+                let callback = u64::from_ne_bytes(args[..8].try_into().unwrap());
+                self.send(callback, 0, &[&0u64.to_ne_bytes()]);
+            }
+            (Object::Seat, 1) => {
+                let capabilities = u64::from_ne_bytes(args[..8].try_into().unwrap());
+                let device = self.new_id(Object::Device);
+                self.send(object, 4, &[&device.to_ne_bytes(), &1u32.to_ne_bytes()]);
+                for (bit, interface) in CAPABILITIES.iter().enumerate() {
+                    if capabilities & 1 << bit != 0 {
+                        let id = self.new_id(Object::Interface(interface));
+                        self.send(
+                            device,
+                            5,
+                            &[&id.to_ne_bytes(), &string(interface), &1u32.to_ne_bytes()],
+                        );
+                    }
+                }
+                self.send(device, 6, &[]);
+                let serial = self.serial();
+                self.send(device, 7, &[&serial]);
+            }
+            (Object::Device, 1) => self.record(Request::StartEmulating),
+            (Object::Device, 2) => self.record(Request::StopEmulating),
+            (Object::Device, 3) => self.record(Request::Frame),
+            (Object::Interface(interface), opcode) => {
+                let request = match (*interface, opcode) {
+                    ("ei_pointer", 1) => Request::MotionRelative {
+                        dx: float(0),
+                        dy: float(1),
+                    },
+                    ("ei_pointer_absolute", 1) => Request::MotionAbsolute {
+                        x: float(0),
+                        y: float(1),
+                    },
+                    ("ei_scroll", 1) => Request::Scroll {
+                        dx: float(0),
+                        dy: float(1),
+                    },
+                    ("ei_scroll", 2) => Request::ScrollDiscrete {
+                        dx: int(0),
+                        dy: int(1),
+                    },
+                    ("ei_scroll", 3) => Request::ScrollStop {
+                        x: uint(0) != 0,
+                        y: uint(1) != 0,
+                        cancel: uint(2) != 0,
+                    },
+                    ("ei_button", 1) => Request::Button {
+                        button: uint(0),
+                        pressed: uint(1) != 0,
+                    },
+                    ("ei_keyboard", 1) => Request::Key {
+                        key: uint(0),
+                        pressed: uint(1) != 0,
+                    },
+                    ("ei_touchscreen", 1) => Request::TouchDown {
+                        id: uint(0),
+                        x: float(1),
+                        y: float(2),
+                    },
+                    ("ei_touchscreen", 2) => Request::TouchMotion {
+                        id: uint(0),
+                        x: float(1),
+                        y: float(2),
+                    },
+                    ("ei_touchscreen", 3) => Request::TouchUp { id: uint(0) },
+                    _ => return,
+                };
+                self.record(request);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// String argument, with its length counting the terminating null and padded to 4 bytes
+fn string(value: &str) -> Vec<u8> {
+    let length = value.len() + 1;
+    let mut bytes = (length as u32).to_ne_bytes().to_vec();
+    bytes.extend(value.as_bytes());
+    bytes.resize(4 + length.next_multiple_of(4), 0);
+    bytes
+}
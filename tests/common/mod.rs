@@ -0,0 +1,53 @@
+// Each test binary only uses some of the helpers
+#![allow(dead_code)]
+
+#[cfg(feature = "portals")]
+pub mod eis;
+#[cfg(feature = "portals")]
+pub mod portal;
+pub mod server;
+
+use libwhydotool::{Event, RecordedEvent, Recorder, Whydotool};
+use std::time::{Duration, Instant};
+use xkbcommon::xkb;
+
+/// Keymap with the `us` layout followed by `de`
+pub fn keymap() -> xkb::Keymap {
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    xkb::Keymap::new_from_string(
+        &context,
+        include_str!("../fixtures/us_de.xkb").to_string(),
+        xkb::KEYMAP_FORMAT_TEXT_V1,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .expect("fixture keymap compiles")
+}
+
+pub fn whydotool() -> (Whydotool, Recorder) {
+    let recorder = Recorder::new();
+    (Whydotool::recording(keymap(), recorder.clone()), recorder)
+}
+
+pub fn events(recorder: &Recorder) -> Vec<Event> {
+    recorder
+        .events()
+        .into_iter()
+        .map(|recorded| recorded.event)
+        .collect()
+}
+
+/// Events at the given milliseconds from the start
+pub fn timed(events: &[(u64, Event)]) -> Vec<RecordedEvent> {
+    let start = Instant::now();
+    events
+        .iter()
+        .map(|(ms, event)| RecordedEvent {
+            time: start + Duration::from_millis(*ms),
+            event: event.clone(),
+        })
+        .collect()
+}
+
+pub const fn key(keycode: u32, pressed: bool) -> Event {
+    Event::Key { keycode, pressed }
+}
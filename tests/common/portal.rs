@@ -0,0 +1,630 @@
+//! Stand-in for xdg-desktop-portal's `RemoteDesktop` and `ScreenCast` interfaces,
+//! served on a private `dbus-daemon` with scripted request responses
+
+use super::eis;
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+use zbus::{
+    message::Header,
+    zvariant::{ObjectPath, OwnedFd, OwnedObjectPath, OwnedValue, Value},
+};
+
+const DESKTOP_PATH: &str = "/org/freedesktop/portal/desktop";
+
+/// How the portal answers a request
+#[derive(Debug, Clone, Copy)]
+pub enum Reply {
+    Accept,
+    /// User dismissed the dialog
+    Cancel,
+    /// Request ended some other way
+    Reject,
+    /// Accepted after the given delay
+    Delay(Duration),
+}
+
+/// Method called on the portal
+#[derive(Debug, Clone, PartialEq)]
+pub enum Call {
+    CreateSession,
+    SelectDevices {
+        types: u32,
+        persist_mode: u32,
+        restore_token: Option<String>,
+    },
+    SelectSources {
+        multiple: bool,
+    },
+    Start,
+    OpenPipeWireRemote,
+    ConnectToEis,
+    NotifyKeyboardKeycode {
+        keycode: i32,
+        state: u32,
+    },
+    NotifyPointerMotion {
+        dx: f64,
+        dy: f64,
+    },
+    NotifyPointerMotionAbsolute {
+        stream: u32,
+        x: f64,
+        y: f64,
+    },
+    NotifyPointerButton {
+        button: i32,
+        state: u32,
+    },
+    NotifyPointerAxis {
+        dx: f64,
+        dy: f64,
+        finish: bool,
+    },
+    NotifyPointerAxisDiscrete {
+        axis: u32,
+        steps: i32,
+    },
+    NotifyTouchDown {
+        stream: u32,
+        slot: u32,
+        x: f64,
+        y: f64,
+    },
+    NotifyTouchMotion {
+        stream: u32,
+        slot: u32,
+        x: f64,
+        y: f64,
+    },
+    NotifyTouchUp {
+        slot: u32,
+    },
+}
+
+pub struct PortalConfig {
+    /// Bitmask of keyboard (1), pointer (2) and touchscreen (4)
+    pub device_types: u32,
+    /// Replies to requests in the order they are made, accepting once exhausted
+    pub replies: Vec<Reply>,
+    /// PipeWire node of the stream returned when sources were selected
+    pub stream_node: u32,
+    /// Whether `ConnectToEIS` hands out a socket to an EIS server
+    pub eis: bool,
+    /// Whether the EIS server offers a seat, without one connecting to it never finishes
+    pub eis_seat: bool,
+}
+
+impl Default for PortalConfig {
+    fn default() -> Self {
+        Self {
+            device_types: 3,
+            replies: Vec::new(),
+            stream_node: 42,
+            eis: false,
+            eis_seat: true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Session {
+    devices: u32,
+    persist_mode: u32,
+    sources: bool,
+    started: bool,
+}
+
+struct PortalState {
+    device_types: u32,
+    stream_node: u32,
+    eis: bool,
+    eis_seat: bool,
+    eis_requests: Arc<Mutex<Vec<eis::Request>>>,
+    replies: VecDeque<Reply>,
+    calls: Vec<Call>,
+    sessions: HashMap<OwnedObjectPath, Session>,
+    issued_tokens: u32,
+}
+
+type SharedState = Arc<Mutex<PortalState>>;
+
+fn lock(state: &SharedState) -> std::sync::MutexGuard<'_, PortalState> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub struct MockPortal {
+    state: SharedState,
+    _service: zbus::blocking::Connection,
+    bus: Child,
+}
+
+impl MockPortal {
+    /// Starts a private bus with the portal on it, returning it with a client connection to the bus.
+    /// Portal tests need `dbus-daemon`, they fail without it rather than passing untested
+    pub fn start(config: PortalConfig) -> (Self, zbus::blocking::Connection) {
+        let mut bus = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("dbus-daemon is needed for portal tests");
+
+        let mut address = String::new();
+        BufReader::new(bus.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim();
+
+        let state = Arc::new(Mutex::new(PortalState {
+            device_types: config.device_types,
+            stream_node: config.stream_node,
+            eis: config.eis,
+            eis_seat: config.eis_seat,
+            eis_requests: Arc::default(),
+            replies: config.replies.into(),
+            calls: Vec::new(),
+            sessions: HashMap::new(),
+            issued_tokens: 0,
+        }));
+
+        let service = zbus::blocking::connection::Builder::address(address)
+            .unwrap()
+            .name("org.freedesktop.portal.Desktop")
+            .unwrap()
+            .serve_at(DESKTOP_PATH, RemoteDesktop(state.clone()))
+            .unwrap()
+            .serve_at(DESKTOP_PATH, ScreenCast(state.clone()))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let client = zbus::blocking::connection::Builder::address(address)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let portal = Self {
+            state,
+            _service: service,
+            bus,
+        };
+
+        (portal, client)
+    }
+
+    /// Calls received so far, oldest first
+    pub fn calls(&self) -> Vec<Call> {
+        lock(&self.state).calls.clone()
+    }
+
+    /// Requests received by the EIS server so far, oldest first
+    pub fn eis_requests(&self) -> Vec<eis::Request> {
+        let requests = lock(&self.state).eis_requests.clone();
+        requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl Drop for MockPortal {
+    fn drop(&mut self) {
+        let _ = self.bus.kill();
+        let _ = self.bus.wait();
+    }
+}
+
+/// Bus name of the caller as used in request and session paths
+fn sender(header: &Header<'_>) -> String {
+    header
+        .sender()
+        .map(|sender| sender.trim_start_matches(':').replace('.', "_"))
+        .unwrap_or_default()
+}
+
+fn string_option(options: &HashMap<String, OwnedValue>, key: &str) -> zbus::fdo::Result<String> {
+    options
+        .get(key)
+        .and_then(|value| String::try_from(value.try_clone().ok()?).ok())
+        .ok_or_else(|| zbus::fdo::Error::InvalidArgs(format!("Missing {key}")))
+}
+
+/// Creates the request for a call, responding to it according to the next scripted reply
+fn respond(
+    state: &SharedState,
+    header: &Header<'_>,
+    conn: &zbus::Connection,
+    options: &HashMap<String, OwnedValue>,
+    results: HashMap<String, OwnedValue>,
+) -> zbus::fdo::Result<OwnedObjectPath> {
+    let request_path = OwnedObjectPath::try_from(format!(
+        "{DESKTOP_PATH}/request/{}/{}",
+        sender(header),
+        string_option(options, "handle_token")?
+    ))
+    .map_err(|e| zbus::fdo::Error::InvalidArgs(e.to_string()))?;
+
+    let reply = lock(state).replies.pop_front().unwrap_or(Reply::Accept);
+    let (response, delay, results) = match reply {
+        Reply::Accept => (0u32, Duration::ZERO, results),
+        Reply::Delay(delay) => (0, delay, results),
+        Reply::Cancel => (1, Duration::ZERO, HashMap::new()),
+        Reply::Reject => (2, Duration::ZERO, HashMap::new()),
+    };
+
+    let destination = header.sender().map(|sender| sender.to_string());
+    let conn = zbus::blocking::Connection::from(conn.clone());
+    let path = request_path.clone();
+    thread::spawn(move || {
+        thread::sleep(delay);
+        let _ = conn.emit_signal(
+            destination.as_deref(),
+            &path,
+            "org.freedesktop.portal.Request",
+            "Response",
+            &(response, results),
+        );
+    });
+
+    Ok(request_path)
+}
+
+/// Fails unless `session_handle` was started with `device` selected
+fn check_session(
+    state: &SharedState,
+    session_handle: &ObjectPath<'_>,
+    device: u32,
+) -> zbus::fdo::Result<()> {
+    let state = lock(state);
+    match state
+        .sessions
+        .get(&OwnedObjectPath::from(session_handle.to_owned()))
+    {
+        Some(session) if session.started && session.devices & device != 0 => Ok(()),
+        _ => Err(zbus::fdo::Error::AccessDenied(
+            "Session not started for this device".to_string(),
+        )),
+    }
+}
+
+struct RemoteDesktop(SharedState);
+
+#[zbus::interface(name = "org.freedesktop.portal.RemoteDesktop")]
+impl RemoteDesktop {
+    #[zbus(property)]
+    fn available_device_types(&self) -> u32 {
+        lock(&self.0).device_types
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        2
+    }
+
+    fn create_session(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        options: HashMap<String, OwnedValue>,
+    ) -> zbus::fdo::Result<OwnedObjectPath> {
+        let session_handle = OwnedObjectPath::try_from(format!(
+            "{DESKTOP_PATH}/session/{}/{}",
+            sender(&header),
+            string_option(&options, "session_handle_token")?
+        ))
+        .map_err(|e| zbus::fdo::Error::InvalidArgs(e.to_string()))?;
+
+        {
+            let mut state = lock(&self.0);
+            state.calls.push(Call::CreateSession);
+            state
+                .sessions
+                .insert(session_handle.clone(), Session::default());
+        }
+
+        let results = [(
+            "session_handle".to_string(),
+            OwnedValue::from(zbus::zvariant::Str::from(
+                session_handle.as_str().to_owned(),
+            )),
+        )]
+        .into();
+        respond(&self.0, &header, conn, &options, results)
+    }
+
+    fn select_devices(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        session_handle: ObjectPath<'_>,
+        options: HashMap<String, OwnedValue>,
+    ) -> zbus::fdo::Result<OwnedObjectPath> {
+        let types = options
+            .get("types")
+            .and_then(|value| u32::try_from(value).ok())
+            .unwrap_or(0);
+        let persist_mode = options
+            .get("persist_mode")
+            .and_then(|value| u32::try_from(value).ok())
+            .unwrap_or(0);
+        let restore_token = string_option(&options, "restore_token").ok();
+
+        {
+            let mut state = lock(&self.0);
+            state.calls.push(Call::SelectDevices {
+                types,
+                persist_mode,
+                restore_token,
+            });
+            if let Some(session) = state
+                .sessions
+                .get_mut(&OwnedObjectPath::from(session_handle.to_owned()))
+            {
+                session.devices = types;
+                session.persist_mode = persist_mode;
+            }
+        }
+
+        respond(&self.0, &header, conn, &options, HashMap::new())
+    }
+
+    fn start(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        session_handle: ObjectPath<'_>,
+        _parent_window: &str,
+        options: HashMap<String, OwnedValue>,
+    ) -> zbus::fdo::Result<OwnedObjectPath> {
+        let mut results = HashMap::new();
+
+        {
+            let mut state = lock(&self.0);
+            state.calls.push(Call::Start);
+            let stream_node = state.stream_node;
+            state.issued_tokens += 1;
+            let restore_token = format!("token-{}", state.issued_tokens);
+            let accepted = matches!(
+                state.replies.front(),
+                None | Some(Reply::Accept | Reply::Delay(_))
+            );
+
+            if let Some(session) = state
+                .sessions
+                .get_mut(&OwnedObjectPath::from(session_handle.to_owned()))
+            {
+                session.started = accepted;
+                results.insert("devices".to_string(), OwnedValue::from(session.devices));
+
+                if session.persist_mode != 0 {
+                    results.insert(
+                        "restore_token".to_string(),
+                        OwnedValue::from(zbus::zvariant::Str::from(restore_token)),
+                    );
+                }
+
+                if session.sources {
+                    let stream_properties: HashMap<String, OwnedValue> = [
+                        (
+                            "position".to_string(),
+                            OwnedValue::try_from(Value::from((0i32, 0i32))).unwrap(),
+                        ),
+                        (
+                            "size".to_string(),
+                            OwnedValue::try_from(Value::from((1920i32, 1080i32))).unwrap(),
+                        ),
+                    ]
+                    .into();
+                    let streams = vec![(stream_node, stream_properties)];
+                    results.insert(
+                        "streams".to_string(),
+                        OwnedValue::try_from(Value::from(streams)).unwrap(),
+                    );
+                }
+            }
+        }
+
+        respond(&self.0, &header, conn, &options, results)
+    }
+
+    #[zbus(name = "ConnectToEIS")]
+    fn connect_to_eis(
+        &self,
+        session_handle: ObjectPath<'_>,
+        _options: HashMap<String, OwnedValue>,
+    ) -> zbus::fdo::Result<OwnedFd> {
+        check_session(&self.0, &session_handle, 7)?;
+        let mut state = lock(&self.0);
+        if !state.eis {
+            return Err(zbus::fdo::Error::NotSupported("No EIS server".to_string()));
+        }
+
+        state.calls.push(Call::ConnectToEis);
+        let socket = eis::serve(state.eis_requests.clone(), state.eis_seat);
+        Ok(std::os::fd::OwnedFd::from(socket).into())
+    }
+
+    fn notify_pointer_motion(
+        &self,
+        session_handle: ObjectPath<'_>,
+        _options: HashMap<String, OwnedValue>,
+        dx: f64,
+        dy: f64,
+    ) -> zbus::fdo::Result<()> {
+        check_session(&self.0, &session_handle, 2)?;
+        lock(&self.0)
+            .calls
+            .push(Call::NotifyPointerMotion { dx, dy });
+        Ok(())
+    }
+
+    fn notify_pointer_motion_absolute(
+        &self,
+        session_handle: ObjectPath<'_>,
+        _options: HashMap<String, OwnedValue>,
+        stream: u32,
+        x: f64,
+        y: f64,
+    ) -> zbus::fdo::Result<()> {
+        check_session(&self.0, &session_handle, 2)?;
+        lock(&self.0)
+            .calls
+            .push(Call::NotifyPointerMotionAbsolute { stream, x, y });
+        Ok(())
+    }
+
+    fn notify_pointer_button(
+        &self,
+        session_handle: ObjectPath<'_>,
+        _options: HashMap<String, OwnedValue>,
+        button: i32,
+        state: u32,
+    ) -> zbus::fdo::Result<()> {
+        check_session(&self.0, &session_handle, 2)?;
+        lock(&self.0)
+            .calls
+            .push(Call::NotifyPointerButton { button, state });
+        Ok(())
+    }
+
+    fn notify_pointer_axis(
+        &self,
+        session_handle: ObjectPath<'_>,
+        options: HashMap<String, OwnedValue>,
+        dx: f64,
+        dy: f64,
+    ) -> zbus::fdo::Result<()> {
+        check_session(&self.0, &session_handle, 2)?;
+        let finish = options
+            .get("finish")
+            .and_then(|value| bool::try_from(&**value).ok())
+            .unwrap_or_default();
+        lock(&self.0)
+            .calls
+            .push(Call::NotifyPointerAxis { dx, dy, finish });
+        Ok(())
+    }
+
+    fn notify_pointer_axis_discrete(
+        &self,
+        session_handle: ObjectPath<'_>,
+        _options: HashMap<String, OwnedValue>,
+        axis: u32,
+        steps: i32,
+    ) -> zbus::fdo::Result<()> {
+        check_session(&self.0, &session_handle, 2)?;
+        lock(&self.0)
+            .calls
+            .push(Call::NotifyPointerAxisDiscrete { axis, steps });
+        Ok(())
+    }
+
+    fn notify_touch_down(
+        &self,
+        session_handle: ObjectPath<'_>,
+        _options: HashMap<String, OwnedValue>,
+        stream: u32,
+        slot: u32,
+        x: f64,
+        y: f64,
+    ) -> zbus::fdo::Result<()> {
+        check_session(&self.0, &session_handle, 4)?;
+        lock(&self.0)
+            .calls
+            .push(Call::NotifyTouchDown { stream, slot, x, y });
+        Ok(())
+    }
+
+    fn notify_touch_motion(
+        &self,
+        session_handle: ObjectPath<'_>,
+        _options: HashMap<String, OwnedValue>,
+        stream: u32,
+        slot: u32,
+        x: f64,
+        y: f64,
+    ) -> zbus::fdo::Result<()> {
+        check_session(&self.0, &session_handle, 4)?;
+        lock(&self.0)
+            .calls
+            .push(Call::NotifyTouchMotion { stream, slot, x, y });
+        Ok(())
+    }
+
+    fn notify_touch_up(
+        &self,
+        session_handle: ObjectPath<'_>,
+        _options: HashMap<String, OwnedValue>,
+        slot: u32,
+    ) -> zbus::fdo::Result<()> {
+        check_session(&self.0, &session_handle, 4)?;
+        lock(&self.0).calls.push(Call::NotifyTouchUp { slot });
+        Ok(())
+    }
+
+    fn notify_keyboard_keycode(
+        &self,
+        session_handle: ObjectPath<'_>,
+        _options: HashMap<String, OwnedValue>,
+        keycode: i32,
+        state: u32,
+    ) -> zbus::fdo::Result<()> {
+        check_session(&self.0, &session_handle, 1)?;
+        lock(&self.0)
+            .calls
+            .push(Call::NotifyKeyboardKeycode { keycode, state });
+        Ok(())
+    }
+}
+
+struct ScreenCast(SharedState);
+
+#[zbus::interface(name = "org.freedesktop.portal.ScreenCast")]
+impl ScreenCast {
+    #[zbus(property)]
+    fn available_source_types(&self) -> u32 {
+        1
+    }
+
+    fn select_sources(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] conn: &zbus::Connection,
+        session_handle: ObjectPath<'_>,
+        options: HashMap<String, OwnedValue>,
+    ) -> zbus::fdo::Result<OwnedObjectPath> {
+        {
+            let mut state = lock(&self.0);
+            let multiple = options
+                .get("multiple")
+                .and_then(|value| bool::try_from(&**value).ok())
+                .unwrap_or_default();
+            state.calls.push(Call::SelectSources { multiple });
+            if let Some(session) = state
+                .sessions
+                .get_mut(&OwnedObjectPath::from(session_handle.to_owned()))
+            {
+                session.sources = true;
+            }
+        }
+
+        respond(&self.0, &header, conn, &options, HashMap::new())
+    }
+
+    fn open_pipe_wire_remote(
+        &self,
+        _session_handle: ObjectPath<'_>,
+        _options: HashMap<String, OwnedValue>,
+    ) -> zbus::fdo::Result<OwnedFd> {
+        lock(&self.0).calls.push(Call::OpenPipeWireRemote);
+        let file = std::fs::File::open("/dev/null")
+            .map_err(|e| zbus::fdo::Error::IOError(e.to_string()))?;
+        Ok(std::os::fd::OwnedFd::from(file).into())
+    }
+}
@@ -0,0 +1,873 @@
+//! Minimal in-process compositor stand-in advertising the globals whydotool uses
+//! and recording every virtual device request it receives
+
+use nix::fcntl::{FcntlArg, FdFlag, fcntl};
+use std::{
+    fs,
+    io::Write,
+    os::{
+        fd::{AsFd, AsRawFd, OwnedFd},
+        unix::{fs::FileExt, net::UnixStream},
+    },
+    process::Command,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+use wayland_client::Connection;
+use wayland_protocols::xdg::xdg_output::zv1::server::{zxdg_output_manager_v1, zxdg_output_v1};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::server::{
+    zwp_virtual_keyboard_manager_v1, zwp_virtual_keyboard_v1,
+};
+use wayland_protocols_wlr::layer_shell::v1::server::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
+use wayland_protocols_wlr::virtual_pointer::v1::server::{
+    zwlr_virtual_pointer_manager_v1, zwlr_virtual_pointer_v1,
+};
+use wayland_server::{
+    Client, DataInit, Dispatch, Display, DisplayHandle, GlobalDispatch, New, Resource,
+    backend::{ClientData, ClientId, DisconnectReason},
+    protocol::{
+        wl_buffer, wl_compositor, wl_keyboard, wl_output, wl_pointer, wl_seat, wl_shm, wl_shm_pool,
+        wl_surface,
+    },
+};
+
+/// Request received from a virtual device
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    Keymap {
+        format: u32,
+        keymap: String,
+    },
+    Key {
+        key: u32,
+        state: u32,
+    },
+    Modifiers {
+        depressed: u32,
+        latched: u32,
+        locked: u32,
+        group: u32,
+    },
+    Motion {
+        dx: f64,
+        dy: f64,
+    },
+    MotionAbsolute {
+        x: u32,
+        y: u32,
+        x_extent: u32,
+        y_extent: u32,
+    },
+    Button {
+        button: u32,
+        pressed: bool,
+    },
+    Axis {
+        axis: wl_pointer::Axis,
+        value: f64,
+    },
+    AxisSource(wl_pointer::AxisSource),
+    AxisStop(wl_pointer::Axis),
+    AxisDiscrete {
+        axis: wl_pointer::Axis,
+        value: f64,
+        discrete: i32,
+    },
+    Frame,
+}
+
+/// Logical placement of an advertised output. Its mode is the logical size multiplied by `scale`,
+/// rotated by `transform`, while `wl_output` only announces the scale rounded up
+#[derive(Debug, Clone, Copy)]
+pub struct OutputConfig {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub scale: f64,
+    pub transform: wl_output::Transform,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1080,
+            scale: 1.0,
+            transform: wl_output::Transform::Normal,
+        }
+    }
+}
+
+pub struct ServerConfig {
+    pub keymap: String,
+    pub repeat_rate: i32,
+    pub outputs: Vec<OutputConfig>,
+    pub virtual_keyboard: bool,
+    pub virtual_pointer: bool,
+    pub virtual_pointer_version: u32,
+    pub xdg_output: bool,
+    pub layer_shell: bool,
+    /// Where the pointer is in the layout, it enters surfaces mapped under it
+    pub pointer: (f64, f64),
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            keymap: include_str!("../fixtures/us_de.xkb").to_string(),
+            repeat_rate: 25,
+            outputs: vec![OutputConfig::default()],
+            virtual_keyboard: true,
+            virtual_pointer: true,
+            virtual_pointer_version: 2,
+            xdg_output: true,
+            layer_shell: true,
+            pointer: (100.0, 200.0),
+        }
+    }
+}
+
+struct ServerState {
+    config: ServerConfig,
+    keymap_file: fs::File,
+    requests: Arc<Mutex<Vec<Request>>>,
+    pointers: Vec<wl_pointer::WlPointer>,
+    serial: u32,
+}
+
+/// Layer surface role of a surface, with whether it was configured and has a buffer
+#[derive(Default)]
+struct SurfaceData(Mutex<Option<LayerRole>>);
+
+struct LayerRole {
+    layer_surface: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+    output: usize,
+    configured: bool,
+    attached: bool,
+}
+
+pub struct TestServer {
+    requests: Arc<Mutex<Vec<Request>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl TestServer {
+    /// Starts the server on its own thread, returning it with a client connection to it
+    pub fn start(config: ServerConfig) -> (Self, Connection) {
+        let (server, client_socket) = Self::start_socket(config);
+        (server, Connection::from_socket(client_socket).unwrap())
+    }
+
+    /// Same as [`Self::start`], returning the client end of the socket for another process to connect with
+    pub fn start_socket(config: ServerConfig) -> (Self, UnixStream) {
+        let (server_socket, client_socket) = UnixStream::pair().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let mut keymap_file = tempfile::tempfile().unwrap();
+        keymap_file.write_all(config.keymap.as_bytes()).unwrap();
+        keymap_file.write_all(b"\0").unwrap();
+
+        let mut state = ServerState {
+            config,
+            keymap_file,
+            requests: requests.clone(),
+            pointers: Vec::new(),
+            serial: 0,
+        };
+
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let mut display = Display::<ServerState>::new().unwrap();
+                let dh = display.handle();
+
+                dh.create_global::<ServerState, wl_seat::WlSeat, ()>(7, ());
+                for (i, _) in state.config.outputs.iter().enumerate() {
+                    dh.create_global::<ServerState, wl_output::WlOutput, usize>(4, i);
+                }
+                if state.config.xdg_output {
+                    dh.create_global::<
+                        ServerState,
+                        zxdg_output_manager_v1::ZxdgOutputManagerV1,
+                        (),
+                    >(3, ());
+                }
+                if state.config.layer_shell {
+                    dh.create_global::<ServerState, wl_compositor::WlCompositor, ()>(4, ());
+                    dh.create_global::<ServerState, wl_shm::WlShm, ()>(1, ());
+                    dh.create_global::<ServerState, zwlr_layer_shell_v1::ZwlrLayerShellV1, ()>(
+                        4,
+                        (),
+                    );
+                }
+                if state.config.virtual_keyboard {
+                    dh.create_global::<
+                        ServerState,
+                        zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1,
+                        (),
+                    >(1, ());
+                }
+                if state.config.virtual_pointer {
+                    dh.create_global::<
+                        ServerState,
+                        zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1,
+                        (),
+                    >(state.config.virtual_pointer_version, ());
+                }
+
+                let client_gone = Arc::new(AtomicBool::new(false));
+                display
+                    .handle()
+                    .insert_client(server_socket, Arc::new(ClientState(client_gone.clone())))
+                    .unwrap();
+
+                while !stop.load(Ordering::Relaxed) && !client_gone.load(Ordering::Relaxed) {
+                    display.dispatch_clients(&mut state).unwrap();
+                    display.flush_clients().unwrap();
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+        });
+
+        let server = Self {
+            requests,
+            stop,
+            thread: Some(thread),
+        };
+
+        (server, client_socket)
+    }
+
+    /// Requests received so far, oldest first
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct ClientState(Arc<AtomicBool>);
+
+impl ClientData for ClientState {
+    fn disconnected(&self, _: ClientId, _: DisconnectReason) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+impl ServerState {
+    fn record(&self, request: Request) {
+        self.requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(request);
+    }
+}
+
+impl GlobalDispatch<wl_seat::WlSeat, ()> for ServerState {
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<wl_seat::WlSeat>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let seat = data_init.init(resource, ());
+        seat.capabilities(wl_seat::Capability::Keyboard | wl_seat::Capability::Pointer);
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for ServerState {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &wl_seat::WlSeat,
+        request: wl_seat::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let wl_seat::Request::GetPointer { id } = request {
+            let pointer = data_init.init(id, ());
+            state.pointers.push(pointer);
+        } else if let wl_seat::Request::GetKeyboard { id } = request {
+            let keyboard = data_init.init(id, ());
+            keyboard.keymap(
+                wl_keyboard::KeymapFormat::XkbV1,
+                state.keymap_file.as_fd(),
+                state.config.keymap.len() as u32 + 1,
+            );
+            if keyboard.version() >= 4 {
+                keyboard.repeat_info(state.config.repeat_rate, 600);
+            }
+        }
+    }
+}
+
+impl Dispatch<wl_pointer::WlPointer, ()> for ServerState {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        pointer: &wl_pointer::WlPointer,
+        request: wl_pointer::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+        if let wl_pointer::Request::Release = request {
+            state.pointers.retain(|p| p != pointer);
+        }
+    }
+}
+
+impl ServerState {
+    /// Maps a committed layer surface, entering it with the pointer when it's over its output
+    fn commit(&mut self, surface: &wl_surface::WlSurface) {
+        let Some(data) = surface.data::<SurfaceData>() else {
+            return;
+        };
+        let mut role = data.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(layer) = role.as_mut() else {
+            return;
+        };
+        let output = self.config.outputs[layer.output];
+
+        if !layer.configured {
+            self.serial += 1;
+            layer
+                .layer_surface
+                .configure(self.serial, output.width as u32, output.height as u32);
+            layer.configured = true;
+            return;
+        }
+
+        let (x, y) = self.config.pointer;
+        let (x, y) = (x - f64::from(output.x), y - f64::from(output.y));
+        let inside = (0.0..f64::from(output.width)).contains(&x)
+            && (0.0..f64::from(output.height)).contains(&y);
+        if layer.attached && inside {
+            for pointer in &self.pointers {
+                self.serial += 1;
+                pointer.enter(self.serial, surface, x, y);
+            }
+        }
+    }
+}
+
+impl GlobalDispatch<wl_compositor::WlCompositor, ()> for ServerState {
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<wl_compositor::WlCompositor>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<wl_compositor::WlCompositor, ()> for ServerState {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let wl_compositor::Request::CreateSurface { id } = request {
+            data_init.init(id, SurfaceData::default());
+        }
+    }
+}
+
+impl Dispatch<wl_surface::WlSurface, SurfaceData> for ServerState {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        surface: &wl_surface::WlSurface,
+        request: wl_surface::Request,
+        data: &SurfaceData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_surface::Request::Attach { buffer, .. } => {
+                if let Some(layer) = data
+                    .0
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .as_mut()
+                {
+                    layer.attached = buffer.is_some();
+                }
+            }
+            wl_surface::Request::Commit => state.commit(surface),
+            _ => {}
+        }
+    }
+}
+
+impl GlobalDispatch<wl_shm::WlShm, ()> for ServerState {
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<wl_shm::WlShm>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let shm = data_init.init(resource, ());
+        shm.format(wl_shm::Format::Argb8888);
+    }
+}
+
+impl Dispatch<wl_shm::WlShm, ()> for ServerState {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &wl_shm::WlShm,
+        request: wl_shm::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let wl_shm::Request::CreatePool { id, .. } = request {
+            data_init.init(id, ());
+        }
+    }
+}
+
+impl Dispatch<wl_shm_pool::WlShmPool, ()> for ServerState {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &wl_shm_pool::WlShmPool,
+        request: wl_shm_pool::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let wl_shm_pool::Request::CreateBuffer { id, .. } = request {
+            data_init.init(id, ());
+        }
+    }
+}
+
+impl Dispatch<wl_buffer::WlBuffer, ()> for ServerState {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &wl_buffer::WlBuffer,
+        _: wl_buffer::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<zwlr_layer_shell_v1::ZwlrLayerShellV1, ()> for ServerState {
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<zwlr_layer_shell_v1::ZwlrLayerShellV1, ()> for ServerState {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &zwlr_layer_shell_v1::ZwlrLayerShellV1,
+        request: zwlr_layer_shell_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let zwlr_layer_shell_v1::Request::GetLayerSurface {
+            id,
+            surface,
+            output,
+            ..
+        } = request
+        {
+            let output = output
+                .and_then(|output| output.data::<usize>().copied())
+                .unwrap_or_default();
+            let layer_surface = data_init.init(id, ());
+            if let Some(data) = surface.data::<SurfaceData>() {
+                *data.0.lock().unwrap_or_else(PoisonError::into_inner) = Some(LayerRole {
+                    layer_surface,
+                    output,
+                    configured: false,
+                    attached: false,
+                });
+            }
+        }
+    }
+}
+
+impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()> for ServerState {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+        _: zwlr_layer_surface_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_keyboard::WlKeyboard, ()> for ServerState {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &wl_keyboard::WlKeyboard,
+        _: wl_keyboard::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<wl_output::WlOutput, usize> for ServerState {
+    fn bind(
+        state: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<wl_output::WlOutput>,
+        index: &usize,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let output = data_init.init(resource, *index);
+        let config = state.config.outputs[*index];
+
+        let width = (f64::from(config.width) * config.scale).round() as i32;
+        let height = (f64::from(config.height) * config.scale).round() as i32;
+        let (width, height) = match config.transform {
+            wl_output::Transform::_90
+            | wl_output::Transform::_270
+            | wl_output::Transform::Flipped90
+            | wl_output::Transform::Flipped270 => (height, width),
+            _ => (width, height),
+        };
+
+        output.geometry(
+            config.x,
+            config.y,
+            0,
+            0,
+            wl_output::Subpixel::Unknown,
+            "whydotool".to_string(),
+            format!("test-{index}"),
+            config.transform,
+        );
+        // A mode that isn't current must be ignored
+        output.mode(wl_output::Mode::empty(), 640, 480, 60_000);
+        output.mode(wl_output::Mode::Current, width, height, 60_000);
+        if output.version() >= 2 {
+            output.scale(config.scale.ceil() as i32);
+        }
+        if output.version() >= 4 {
+            output.name(format!("TEST-{index}"));
+        }
+        if output.version() >= 2 {
+            output.done();
+        }
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, usize> for ServerState {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &wl_output::WlOutput,
+        _: wl_output::Request,
+        _: &usize,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<zxdg_output_manager_v1::ZxdgOutputManagerV1, ()> for ServerState {
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<zxdg_output_manager_v1::ZxdgOutputManagerV1>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<zxdg_output_manager_v1::ZxdgOutputManagerV1, ()> for ServerState {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &zxdg_output_manager_v1::ZxdgOutputManagerV1,
+        request: zxdg_output_manager_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let zxdg_output_manager_v1::Request::GetXdgOutput { id, output } = request {
+            let xdg_output = data_init.init(id, ());
+            let Some(index) = output.data::<usize>().copied() else {
+                return;
+            };
+            let config = state.config.outputs[index];
+
+            xdg_output.logical_position(config.x, config.y);
+            xdg_output.logical_size(config.width, config.height);
+            if xdg_output.version() >= 2 {
+                xdg_output.name(format!("TEST-{index}"));
+            }
+            if xdg_output.version() < 3 {
+                xdg_output.done();
+            } else {
+                output.done();
+            }
+        }
+    }
+}
+
+impl Dispatch<zxdg_output_v1::ZxdgOutputV1, ()> for ServerState {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &zxdg_output_v1::ZxdgOutputV1,
+        _: zxdg_output_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1, ()>
+    for ServerState
+{
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1, ()> for ServerState {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1,
+        request: zwp_virtual_keyboard_manager_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let zwp_virtual_keyboard_manager_v1::Request::CreateVirtualKeyboard { id, .. } = request
+        {
+            data_init.init(id, ());
+        }
+    }
+}
+
+impl Dispatch<zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1, ()> for ServerState {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1,
+        request: zwp_virtual_keyboard_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwp_virtual_keyboard_v1::Request::Keymap { format, fd, size } => {
+                state.record(Request::Keymap {
+                    format,
+                    keymap: read_keymap(fd, size),
+                });
+            }
+            zwp_virtual_keyboard_v1::Request::Key { key, state: s, .. } => {
+                state.record(Request::Key { key, state: s });
+            }
+            zwp_virtual_keyboard_v1::Request::Modifiers {
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+            } => state.record(Request::Modifiers {
+                depressed: mods_depressed,
+                latched: mods_latched,
+                locked: mods_locked,
+                group,
+            }),
+            _ => {}
+        }
+    }
+}
+
+impl GlobalDispatch<zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1, ()>
+    for ServerState
+{
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1, ()> for ServerState {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1,
+        request: zwlr_virtual_pointer_manager_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointer { id, .. }
+            | zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointerWithOutput {
+                id, ..
+            } => {
+                data_init.init(id, ());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<zwlr_virtual_pointer_v1::ZwlrVirtualPointerV1, ()> for ServerState {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &zwlr_virtual_pointer_v1::ZwlrVirtualPointerV1,
+        request: zwlr_virtual_pointer_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_virtual_pointer_v1::Request::Motion { dx, dy, .. } => {
+                state.record(Request::Motion { dx, dy });
+            }
+            zwlr_virtual_pointer_v1::Request::MotionAbsolute {
+                x,
+                y,
+                x_extent,
+                y_extent,
+                ..
+            } => state.record(Request::MotionAbsolute {
+                x,
+                y,
+                x_extent,
+                y_extent,
+            }),
+            zwlr_virtual_pointer_v1::Request::Button {
+                button, state: s, ..
+            } => state.record(Request::Button {
+                button,
+                pressed: matches!(s.into_result(), Ok(wl_pointer::ButtonState::Pressed)),
+            }),
+            zwlr_virtual_pointer_v1::Request::Axis { axis, value, .. } => {
+                if let Ok(axis) = axis.into_result() {
+                    state.record(Request::Axis { axis, value });
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::AxisSource { axis_source } => {
+                if let Ok(axis_source) = axis_source.into_result() {
+                    state.record(Request::AxisSource(axis_source));
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::AxisStop { axis, .. } => {
+                if let Ok(axis) = axis.into_result() {
+                    state.record(Request::AxisStop(axis));
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::AxisDiscrete {
+                axis,
+                value,
+                discrete,
+                ..
+            } => {
+                if let Ok(axis) = axis.into_result() {
+                    state.record(Request::AxisDiscrete {
+                        axis,
+                        value,
+                        discrete,
+                    });
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::Frame => state.record(Request::Frame),
+            _ => {}
+        }
+    }
+}
+
+/// Command running the whydotool binary connected to the server through `socket`
+pub fn whydotool_command(socket: &UnixStream) -> Command {
+    // Inherited by the child, which takes over the connection
+    fcntl(socket, FcntlArg::F_SETFD(FdFlag::empty())).unwrap();
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_whydotool"));
+    command
+        .env("WAYLAND_SOCKET", socket.as_raw_fd().to_string())
+        .env_remove("WAYLAND_DISPLAY");
+    command
+}
+
+/// Reads a keymap sent by the client, without the trailing NUL
+fn read_keymap(fd: OwnedFd, size: u32) -> String {
+    let mut keymap = vec![0; size as usize];
+    // The client may have left the offset at the end of the file
+    fs::File::from(fd).read_exact_at(&mut keymap, 0).unwrap();
+
+    String::from_utf8_lossy(&keymap)
+        .trim_end_matches('\0')
+        .to_string()
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use common::server::{Request, ServerConfig, TestServer, whydotool_command};
+use std::{
+    os::unix::net::UnixStream,
+    path::Path,
+    process::{Child, Command, Output},
+    thread,
+    time::{Duration, Instant},
+};
+
+const KEY_A: u32 = 30;
+const KEY_B: u32 = 48;
+
+fn whydotool(runtime_dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_whydotool"))
+        .args(args)
+        .env("XDG_RUNTIME_DIR", runtime_dir)
+        .env_remove("WAYLAND_DISPLAY")
+        .env_remove("WAYLAND_SOCKET")
+        .output()
+        .unwrap()
+}
+
+/// Starts a daemon connected to `socket`, returning it with the path it listens on
+fn start_daemon(socket: &UnixStream, runtime_dir: &Path) -> (Child, std::path::PathBuf) {
+    let daemon = whydotool_command(socket)
+        .arg("daemon")
+        .env("XDG_RUNTIME_DIR", runtime_dir)
+        .spawn()
+        .unwrap();
+
+    let socket_path = runtime_dir.join("whydotool-wayland-0.sock");
+    let started = Instant::now();
+    while !socket_path.exists() && started.elapsed() < Duration::from_secs(10) {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    (daemon, socket_path)
+}
+
+#[test]
+fn commands_are_forwarded_to_daemon() {
+    let (server, socket) = TestServer::start_socket(ServerConfig::default());
+    let runtime_dir = tempfile::tempdir().unwrap();
+
+    // Only the daemon is connected to the server, clients have no compositor to connect to
+    let (mut daemon, _) = start_daemon(&socket, runtime_dir.path());
+
+    let key = whydotool(runtime_dir.path(), &["key", "-d", "0", "a"]);
+    let typed = whydotool(runtime_dir.path(), &["type", "-d", "0", "-H", "0", "b"]);
+    let unknown = whydotool(runtime_dir.path(), &["key", "nosuchkey"]);
+    let _ = daemon.kill();
+    let _ = daemon.wait();
+
+    assert!(key.status.success(), "{key:?}");
+    assert!(typed.status.success(), "{typed:?}");
+    assert!(!unknown.status.success());
+    assert!(String::from_utf8_lossy(&unknown.stderr).contains("Unknown key 'nosuchkey'"));
+
+    let requests = server.requests();
+    let keymaps = requests
+        .iter()
+        .filter(|request| matches!(request, Request::Keymap { .. }))
+        .count();
+    let keys = requests
+        .iter()
+        .filter_map(|request| match request {
+            Request::Key { key, state } => Some((*key, *state)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    // Both commands went through the one keyboard the daemon created
+    assert_eq!(keymaps, 1);
+    assert_eq!(keys, [(KEY_A, 1), (KEY_A, 0), (KEY_B, 1), (KEY_B, 0)]);
+}
+
+#[test]
+fn idle_clients_dont_hold_up_the_daemon() {
+    let (_server, socket) = TestServer::start_socket(ServerConfig::default());
+    let runtime_dir = tempfile::tempdir().unwrap();
+    let (mut daemon, socket_path) = start_daemon(&socket, runtime_dir.path());
+
+    // Connected without ever finishing a request
+    let idle = UnixStream::connect(&socket_path).unwrap();
+    let key = whydotool(runtime_dir.path(), &["key", "-d", "0", "a"]);
+    drop(idle);
+    let _ = daemon.kill();
+    let _ = daemon.wait();
+
+    assert!(key.status.success(), "{key:?}");
+}
+
+#[cfg(feature = "portals")]
+#[test]
+fn global_flags_are_rejected_by_the_daemon() {
+    use std::{
+        io::{Read, Write},
+        net::Shutdown,
+    };
+
+    let (server, socket) = TestServer::start_socket(ServerConfig::default());
+    let runtime_dir = tempfile::tempdir().unwrap();
+    let (mut daemon, socket_path) = start_daemon(&socket, runtime_dir.path());
+
+    let mut stream = UnixStream::connect(&socket_path).unwrap();
+    stream.write_all(b"/\0--force-portal\0key\0a\0").unwrap();
+    stream.shutdown(Shutdown::Write).unwrap();
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).unwrap();
+    let _ = daemon.kill();
+    let _ = daemon.wait();
+
+    assert_eq!(reply.first(), Some(&1));
+    assert!(String::from_utf8_lossy(&reply).contains("Global flags"));
+    assert!(
+        !server
+            .requests()
+            .iter()
+            .any(|request| matches!(request, Request::Key { .. }))
+    );
+}
+
+#[test]
+fn endless_loops_run_in_the_client() {
+    let (server, socket) = TestServer::start_socket(ServerConfig::default());
+    let runtime_dir = tempfile::tempdir().unwrap();
+    let (mut daemon, _) = start_daemon(&socket, runtime_dir.path());
+
+    let script = runtime_dir.path().join("loop.wdt");
+    std::fs::write(&script, "key -d 0 a\n").unwrap();
+    // Not forwarded, the client has no compositor to run it with
+    let looped = whydotool(
+        runtime_dir.path(),
+        &["run", "--loop", "0", script.to_str().unwrap()],
+    );
+    let _ = daemon.kill();
+    let _ = daemon.wait();
+
+    assert!(!looped.status.success());
+    assert!(
+        !server
+            .requests()
+            .iter()
+            .any(|request| matches!(request, Request::Key { .. }))
+    );
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use common::server::{Request, ServerConfig, TestServer, whydotool_command};
+use libwhydotool::button_code;
+use nix::{
+    sys::signal::{Signal, kill},
+    unistd::Pid,
+};
+use std::{
+    process::Output,
+    thread,
+    time::{Duration, Instant},
+};
+
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+
+fn drag(args: &[&str]) -> (Output, Vec<Request>) {
+    let (server, socket) = TestServer::start_socket(ServerConfig::default());
+
+    let output = whydotool_command(&socket)
+        .arg("drag")
+        .args(args)
+        .output()
+        .unwrap();
+
+    let requests = server.requests();
+    (output, requests)
+}
+
+fn absolute(x: u32, y: u32) -> Request {
+    Request::MotionAbsolute {
+        x,
+        y,
+        x_extent: 1920,
+        y_extent: 1080,
+    }
+}
+
+fn button(button: u32, pressed: bool) -> Request {
+    Request::Button { button, pressed }
+}
+
+#[test]
+fn button_names() {
+    assert_eq!(button_code("left"), Some(BTN_LEFT));
+    assert_eq!(button_code("Right"), Some(BTN_RIGHT));
+    assert_eq!(button_code("task"), Some(0x117));
+    assert_eq!(button_code("0x113"), Some(0x113));
+    assert_eq!(button_code("274"), Some(0x112));
+    assert_eq!(button_code("wheel"), None);
+    assert_eq!(button_code("BTN_side"), Some(0x113));
+    assert_eq!(button_code("30"), None);
+}
+
+#[test]
+fn holds_the_button_through_the_move() {
+    let (output, requests) = drag(&[
+        "--from",
+        "100,100",
+        "--to",
+        "50%,50%",
+        "--duration",
+        "0",
+        "--steps",
+        "2",
+    ]);
+
+    assert!(output.status.success(), "{output:?}");
+    let requests: Vec<_> = requests
+        .into_iter()
+        .filter(|request| *request != Request::Frame)
+        .collect();
+    assert_eq!(
+        requests,
+        [
+            absolute(100, 100),
+            button(BTN_LEFT, true),
+            absolute(530, 320),
+            absolute(960, 540),
+            button(BTN_LEFT, false),
+        ]
+    );
+}
+
+#[test]
+fn other_buttons_and_unknown_ones() {
+    let (output, requests) = drag(&[
+        "--from",
+        "0,0",
+        "--to",
+        "10,10",
+        "--duration",
+        "0",
+        "--button",
+        "right",
+    ]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(requests.contains(&button(BTN_RIGHT, true)));
+    assert!(requests.contains(&button(BTN_RIGHT, false)));
+
+    let (output, requests) = drag(&["--from", "0,0", "--to", "10,10", "--button", "wheel"]);
+    assert!(!output.status.success());
+    assert!(requests.is_empty());
+}
+
+#[test]
+fn interrupted_drag_releases_the_button() {
+    let (server, socket) = TestServer::start_socket(ServerConfig::default());
+
+    let child = whydotool_command(&socket)
+        .args([
+            "drag",
+            "--from",
+            "0,0",
+            "--to",
+            "1000,0",
+            "--duration",
+            "10s",
+            "--steps",
+            "1",
+        ])
+        .spawn()
+        .unwrap();
+
+    // Give it time to press the button and wait for its only step
+    thread::sleep(Duration::from_millis(500));
+    let interrupted = Instant::now();
+    kill(Pid::from_raw(child.id() as i32), Signal::SIGINT).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(!output.status.success());
+    assert!(interrupted.elapsed() < Duration::from_secs(2));
+
+    let buttons: Vec<_> = server
+        .requests()
+        .into_iter()
+        .filter(|request| matches!(request, Request::Button { .. }))
+        .collect();
+    assert_eq!(buttons, [button(BTN_LEFT, true), button(BTN_LEFT, false)]);
+}
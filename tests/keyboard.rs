@@ -0,0 +1,142 @@
+mod common;
+
+use libwhydotool::{Error, Result, TextToken, VirtualKeyboard, type_tokens};
+use std::time::Duration;
+use xkbcommon::xkb;
+
+// evdev keycodes, xkb keycodes are offset by 8
+const KEY_2: u32 = 3;
+const KEY_Q: u32 = 16;
+const KEY_Y: u32 = 21;
+const KEY_A: u32 = 30;
+const KEY_APOSTROPHE: u32 = 40;
+const KEY_LEFTSHIFT: u32 = 42;
+const KEY_Z: u32 = 44;
+
+fn keycode(evdev: u32) -> xkb::Keycode {
+    xkb::Keycode::new(evdev + 8)
+}
+
+#[test]
+fn char_on_base_level() {
+    let (whydotool, _) = common::whydotool();
+    let mut keyboard = whydotool.virtual_keyboard().unwrap();
+
+    let char_key = keyboard.keycode_from_char('a').unwrap();
+    assert_eq!(char_key.keycode, keycode(KEY_A));
+    assert_eq!(char_key.mods, 0);
+    assert_eq!(char_key.layout, 0);
+}
+
+#[test]
+fn char_on_shift_level() {
+    let (whydotool, _) = common::whydotool();
+    let mut keyboard = whydotool.virtual_keyboard().unwrap();
+
+    let char_key = keyboard.keycode_from_char('@').unwrap();
+    assert_eq!(char_key.keycode, keycode(KEY_2));
+    assert_eq!(char_key.layout, 0);
+
+    let modifiers = keyboard.modifier_keycodes(char_key.mods).unwrap();
+    assert_eq!(modifiers, [keycode(KEY_LEFTSHIFT)]);
+}
+
+#[test]
+fn char_from_other_layout() {
+    let (whydotool, _) = common::whydotool();
+    let mut keyboard = whydotool.virtual_keyboard().unwrap();
+
+    let char_key = keyboard.keycode_from_char('ä').unwrap();
+    assert_eq!(char_key.keycode, keycode(KEY_APOSTROPHE));
+    assert_eq!(char_key.mods, 0);
+    assert_eq!(char_key.layout, 1);
+}
+
+#[test]
+fn char_prefers_active_layout() {
+    let (whydotool, _) = common::whydotool();
+    let mut keyboard = whydotool.virtual_keyboard().unwrap();
+
+    // y and z are swapped on the German layout
+    assert_eq!(
+        keyboard.keycode_from_char('z').unwrap().keycode,
+        keycode(KEY_Z)
+    );
+
+    assert!(keyboard.lock_layout(1));
+    let char_key = keyboard.keycode_from_char('z').unwrap();
+    assert_eq!(char_key.keycode, keycode(KEY_Y));
+    assert_eq!(char_key.layout, 1);
+
+    // @ is AltGr+Q on the German layout
+    let char_key = keyboard.keycode_from_char('@').unwrap();
+    assert_eq!(char_key.keycode, keycode(KEY_Q));
+    assert_eq!(char_key.layout, 1);
+    assert!(keyboard.modifier_keycodes(char_key.mods).is_some());
+}
+
+#[test]
+fn newline_is_return() {
+    let (whydotool, _) = common::whydotool();
+    let mut keyboard = whydotool.virtual_keyboard().unwrap();
+
+    let char_key = keyboard.keycode_from_char('\n').unwrap();
+    assert_eq!(
+        keyboard.xkb_state().key_get_one_sym(char_key.keycode),
+        xkb::Keysym::Return
+    );
+}
+
+#[test]
+fn unmapped_char() {
+    let (whydotool, _) = common::whydotool();
+    let mut keyboard = whydotool.virtual_keyboard().unwrap();
+
+    assert!(keyboard.keycode_from_char('☃').is_none());
+
+    keyboard.map_chars(&['☃']).unwrap();
+    let char_key = keyboard.keycode_from_char('☃').unwrap();
+    assert!(char_key.keycode.raw() <= 255);
+
+    keyboard.restore_keymap().unwrap();
+    assert!(keyboard.keycode_from_char('☃').is_none());
+}
+
+/// Keyboard whose keymap has no keys for any modifier
+struct NoModifiers {
+    keyboard: Box<dyn VirtualKeyboard>,
+    keys: Vec<xkb::Keycode>,
+}
+
+impl VirtualKeyboard for NoModifiers {
+    fn xkb_state(&mut self) -> &mut xkb::State {
+        self.keyboard.xkb_state()
+    }
+
+    fn modifier_keycodes(&mut self, _mods: xkb::ModMask) -> Option<Vec<xkb::Keycode>> {
+        None
+    }
+
+    fn key(&mut self, key: xkb::Keycode, _state: xkb::KeyDirection) -> Result<()> {
+        self.keys.push(key);
+        Ok(())
+    }
+}
+
+#[test]
+fn chars_needing_missing_modifiers_are_untypable() {
+    let (whydotool, _) = common::whydotool();
+    let mut keyboard = NoModifiers {
+        keyboard: whydotool.virtual_keyboard().unwrap(),
+        keys: Vec::new(),
+    };
+
+    let result = type_tokens(
+        &mut keyboard,
+        &[TextToken::Char('A')],
+        Duration::ZERO,
+        || Ok(()),
+    );
+    assert!(matches!(result, Err(Error::UntypableChar('A'))));
+    assert!(keyboard.keys.is_empty());
+}
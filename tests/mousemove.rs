@@ -0,0 +1,228 @@
+mod common;
+
+use common::server::{OutputConfig, Request, ServerConfig, TestServer, whydotool_command};
+use libwhydotool::{Coordinate, Easing, SmoothMotion};
+use std::{
+    process::Output,
+    time::{Duration, Instant},
+};
+
+fn mousemove(args: &[&str]) -> (Output, Vec<Request>) {
+    let (server, socket) = TestServer::start_socket(ServerConfig {
+        outputs: vec![
+            OutputConfig::default(),
+            OutputConfig {
+                x: 1920,
+                y: 0,
+                width: 1280,
+                height: 1024,
+                ..OutputConfig::default()
+            },
+        ],
+        ..ServerConfig::default()
+    });
+
+    let output = whydotool_command(&socket)
+        .arg("mousemove")
+        .args(args)
+        .output()
+        .unwrap();
+
+    let requests = server.requests();
+    (output, requests)
+}
+
+fn absolute(x: u32, y: u32) -> Request {
+    Request::MotionAbsolute {
+        x,
+        y,
+        x_extent: 3200,
+        y_extent: 1080,
+    }
+}
+
+#[test]
+fn resolve_coordinates() {
+    assert_eq!("50%".parse(), Ok(Coordinate::Percent(50.0)));
+    assert_eq!("-2.5".parse(), Ok(Coordinate::Pixels(-2.5)));
+    assert!("%".parse::<Coordinate>().is_err());
+
+    assert_eq!(Coordinate::Percent(50.0).resolve(1920), Some(960));
+    assert_eq!(Coordinate::Percent(100.0).resolve(1920), Some(1919));
+    assert_eq!(Coordinate::Percent(120.0).resolve(1920), None);
+    assert_eq!(Coordinate::Pixels(100.0).resolve(0), Some(100));
+    assert_eq!(Coordinate::Pixels(1.5).resolve(0), None);
+    assert_eq!(Coordinate::Pixels(-1.0).resolve(0), None);
+}
+
+#[test]
+fn percentages_of_the_layout() {
+    let (output, requests) = mousemove(&["--absolute", "-x", "50%", "-y", "540"]);
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(requests, [absolute(1600, 540), Request::Frame]);
+}
+
+#[test]
+fn percentages_of_an_output() {
+    let (output, requests) = mousemove(&["-a", "-o", "TEST-1", "-x", "50%", "-y", "25%"]);
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(requests, [absolute(2560, 256), Request::Frame]);
+}
+
+#[test]
+fn fractional_and_relative_percentages_are_rejected() {
+    let (output, requests) = mousemove(&["--absolute", "-x", "10.5", "-y", "0"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'10.5'"));
+
+    let (output, _) = mousemove(&["-x", "50%", "-y", "0"]);
+    assert!(!output.status.success());
+
+    assert!(requests.is_empty());
+}
+
+#[test]
+fn absolute_wheel_is_rejected() {
+    let (output, requests) = mousemove(&["-w", "-a", "-x", "0", "-y", "1"]);
+
+    assert!(!output.status.success());
+    assert!(requests.is_empty());
+}
+
+#[test]
+fn pointer_position_is_printed() {
+    let (server, socket) = TestServer::start_socket(ServerConfig {
+        pointer: (960.0, 540.0),
+        ..ServerConfig::default()
+    });
+
+    let output = whydotool_command(&socket)
+        .arg("pointer-position")
+        .output()
+        .unwrap();
+    drop(server);
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "x:960 y:540 output:TEST-0 output_x:960 output_y:540\n"
+    );
+}
+
+fn motions(requests: &[Request]) -> Vec<(f64, f64)> {
+    requests
+        .iter()
+        .filter_map(|request| match request {
+            Request::Motion { dx, dy } => Some((*dx, *dy)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn easing_curves() {
+    for easing in [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ] {
+        assert_eq!(easing.apply(0.0), 0.0);
+        assert_eq!(easing.apply(1.0), 1.0);
+    }
+
+    assert!(Easing::EaseIn.apply(0.25) < 0.25);
+    assert!(Easing::EaseOut.apply(0.25) > 0.25);
+    assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    assert_eq!("ease-in-out".parse(), Ok(Easing::EaseInOut));
+    assert!("bounce".parse::<Easing>().is_err());
+}
+
+#[test]
+fn paths_end_at_the_target() {
+    let line = SmoothMotion::new(Duration::ZERO).steps(4);
+    assert_eq!(
+        line.path((0.0, 0.0), (100.0, 40.0)),
+        [(25.0, 10.0), (50.0, 20.0), (75.0, 30.0), (100.0, 40.0)]
+    );
+
+    // Halfway along a curve with one control point is halfway between the control and the line
+    let curve = SmoothMotion::new(Duration::ZERO)
+        .steps(2)
+        .control(50.0, 100.0);
+    assert_eq!(
+        curve.path((10.0, 10.0), (110.0, 10.0)),
+        [(60.0, 60.0), (110.0, 10.0)]
+    );
+}
+
+#[test]
+fn gradual_relative_move() {
+    let start = Instant::now();
+    let (output, requests) = mousemove(&[
+        "-x",
+        "100",
+        "-y",
+        "-40",
+        "--duration",
+        "60ms",
+        "--steps",
+        "4",
+        "--easing",
+        "ease-in-out",
+    ]);
+
+    assert!(output.status.success(), "{output:?}");
+    assert!(start.elapsed() >= Duration::from_millis(60));
+
+    let motions = motions(&requests);
+    assert_eq!(motions.len(), 4);
+    let (x, y) = motions
+        .iter()
+        .fold((0.0, 0.0), |(x, y), (dx, dy)| (x + dx, y + dy));
+    assert!((x - 100.0_f64).abs() < 1e-9 && (y + 40.0_f64).abs() < 1e-9);
+}
+
+#[test]
+fn gradual_absolute_move_on_an_output() {
+    let (output, requests) = mousemove(&[
+        "-a",
+        "-o",
+        "TEST-1",
+        "--from",
+        "0,0",
+        "-x",
+        "50%",
+        "-y",
+        "600",
+        "--duration",
+        "0",
+        "--steps",
+        "2",
+    ]);
+
+    assert!(output.status.success(), "{output:?}");
+    let positions: Vec<_> = requests
+        .iter()
+        .filter_map(|request| match request {
+            Request::MotionAbsolute { x, y, .. } => Some((*x, *y)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(positions, [(2240, 300), (2560, 600)]);
+}
+
+#[test]
+fn gradual_absolute_move_needs_a_start() {
+    let (output, requests) = mousemove(&["-a", "-x", "0", "-y", "0", "--duration", "0"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--from"));
+    assert!(
+        !requests
+            .iter()
+            .any(|request| matches!(request, Request::MotionAbsolute { .. }))
+    );
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use common::{events, key, timed, whydotool};
+use libwhydotool::{Event, Player, RecordedEvent};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+const KEY_A: u32 = 30;
+const BTN_LEFT: u32 = 0x110;
+
+fn play(player: Player, recorded: &[RecordedEvent]) -> (Vec<Event>, Duration) {
+    let (whydotool, recorder) = whydotool();
+    let mut virtual_keyboard = whydotool.virtual_keyboard().unwrap();
+    let virtual_pointer = whydotool.virtual_pointer().unwrap();
+
+    let start = Instant::now();
+    player
+        .play(recorded, &mut *virtual_keyboard, &*virtual_pointer, || {
+            Ok(())
+        })
+        .unwrap();
+
+    (events(&recorder), start.elapsed())
+}
+
+#[test]
+fn plays_events_in_order() {
+    let recorded = timed(&[
+        (0, key(KEY_A, true)),
+        (10, key(KEY_A, false)),
+        (20, Event::Motion { x: 5.0, y: -5.0 }),
+        (
+            30,
+            Event::Button {
+                button: BTN_LEFT,
+                pressed: true,
+            },
+        ),
+        (
+            40,
+            Event::Button {
+                button: BTN_LEFT,
+                pressed: false,
+            },
+        ),
+    ]);
+
+    let (played, _) = play(Player::new().loops(2), &recorded);
+
+    let once = recorded.into_iter().map(|recorded| recorded.event);
+    assert_eq!(played, once.clone().chain(once).collect::<Vec<_>>());
+}
+
+#[test]
+fn delays_are_scaled() {
+    let recorded = timed(&[(0, key(KEY_A, true)), (200, key(KEY_A, false))]);
+
+    let (_, elapsed) = play(Player::new(), &recorded);
+    assert!(elapsed >= Duration::from_millis(200), "{elapsed:?}");
+
+    let (_, elapsed) = play(Player::new().speed(4.0), &recorded);
+    assert!(elapsed >= Duration::from_millis(50), "{elapsed:?}");
+    assert!(elapsed < Duration::from_millis(200), "{elapsed:?}");
+
+    let (played, elapsed) = play(Player::new().delays(false), &recorded);
+    assert_eq!(played.len(), 2);
+    assert!(elapsed < Duration::from_millis(50), "{elapsed:?}");
+}
+
+#[test]
+fn jitter_stays_in_range() {
+    let player = Player::new().speed(2.0).jitter(Duration::from_millis(10));
+
+    for _ in 0..100 {
+        let delay = player.delay(Duration::from_millis(100));
+        assert!(delay >= Duration::from_millis(40), "{delay:?}");
+        assert!(delay <= Duration::from_millis(60), "{delay:?}");
+    }
+
+    // Jitter never makes a delay negative
+    assert!(player.delay(Duration::ZERO) <= Duration::from_millis(10));
+}
+
+#[test]
+fn schedule_absorbs_time_between_waits() {
+    let mut schedule = Player::new().schedule();
+    let start = Instant::now();
+
+    // Work taking longer than a wait doesn't push back the waits after it
+    thread::sleep(Duration::from_millis(150));
+    schedule.wait(Duration::from_millis(100));
+    schedule.wait(Duration::from_millis(100));
+
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(200), "{elapsed:?}");
+    assert!(elapsed < Duration::from_millis(300), "{elapsed:?}");
+}
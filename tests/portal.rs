@@ -0,0 +1,632 @@
+#![cfg(feature = "portals")]
+
+mod common;
+
+use common::{
+    eis::Request as EisRequest,
+    portal::{Call, MockPortal, PortalConfig, Reply},
+    server::{OutputConfig, ServerConfig, TestServer},
+};
+use libwhydotool::{
+    Error, RestoreTokens, ScrollSource, SmoothMotion, TextToken, Whydotool, type_tokens,
+};
+use std::{fs, os::unix::fs::PermissionsExt, time::Duration};
+use wayland_client::protocol::wl_pointer::ButtonState;
+
+const KEY_A: i32 = 30;
+const KEY_LEFTSHIFT: i32 = 42;
+const BTN_LEFT: u32 = 0x110;
+
+/// Whydotool forced onto the portal, with the Wayland server only providing the keymap
+fn connect(config: PortalConfig) -> (TestServer, MockPortal, Whydotool) {
+    let (portal, bus) = MockPortal::start(config);
+    let (server, conn) = TestServer::start(ServerConfig::default());
+
+    let mut whydotool = Whydotool::try_from_connection(&conn).unwrap();
+    whydotool.force_portal(true);
+    whydotool.portal_connection(bus);
+    whydotool.restore_tokens(None);
+
+    (server, portal, whydotool)
+}
+
+#[test]
+fn keyboard_notifies_keycodes() {
+    let (_server, portal, whydotool) = connect(PortalConfig::default());
+
+    let mut keyboard = whydotool.virtual_keyboard().unwrap();
+    let tokens = [TextToken::Char('A')];
+    type_tokens(keyboard.as_mut(), &tokens, Duration::ZERO, || Ok(())).unwrap();
+
+    assert_eq!(
+        portal.calls(),
+        [
+            Call::CreateSession,
+            Call::SelectDevices {
+                types: 1,
+                persist_mode: 0,
+                restore_token: None,
+            },
+            Call::Start,
+            Call::NotifyKeyboardKeycode {
+                keycode: KEY_LEFTSHIFT,
+                state: 1,
+            },
+            Call::NotifyKeyboardKeycode {
+                keycode: KEY_A,
+                state: 1,
+            },
+            Call::NotifyKeyboardKeycode {
+                keycode: KEY_A,
+                state: 0,
+            },
+            Call::NotifyKeyboardKeycode {
+                keycode: KEY_LEFTSHIFT,
+                state: 0,
+            },
+        ]
+    );
+}
+
+#[test]
+fn untypable_chars_are_an_error() {
+    let (_server, portal, whydotool) = connect(PortalConfig::default());
+
+    // The portal can neither lock the `de` layout nor replace the keymap
+    let mut keyboard = whydotool.virtual_keyboard().unwrap();
+    let tokens = [TextToken::Char('a'), TextToken::Char('ä')];
+    let result = type_tokens(keyboard.as_mut(), &tokens, Duration::ZERO, || Ok(()));
+
+    assert!(matches!(result, Err(Error::UntypableChar('ä'))));
+    assert_eq!(
+        portal.calls()[3..],
+        [
+            Call::NotifyKeyboardKeycode {
+                keycode: KEY_A,
+                state: 1,
+            },
+            Call::NotifyKeyboardKeycode {
+                keycode: KEY_A,
+                state: 0,
+            },
+        ]
+    );
+}
+
+#[test]
+fn pointer_notifies_button_motion_and_axis() {
+    let (_server, portal, whydotool) = connect(PortalConfig::default());
+
+    let pointer = whydotool.virtual_pointer().unwrap();
+    pointer.button(BTN_LEFT, ButtonState::Pressed).unwrap();
+    pointer.button(BTN_LEFT, ButtonState::Released).unwrap();
+    pointer.motion(10.0, -5.0).unwrap();
+    pointer.scroll(0.0, 15.0).unwrap();
+
+    assert_eq!(
+        portal.calls(),
+        [
+            Call::CreateSession,
+            Call::SelectDevices {
+                types: 2,
+                persist_mode: 0,
+                restore_token: None,
+            },
+            Call::SelectSources { multiple: false },
+            Call::Start,
+            Call::NotifyPointerButton {
+                button: BTN_LEFT as i32,
+                state: 1,
+            },
+            Call::NotifyPointerButton {
+                button: BTN_LEFT as i32,
+                state: 0,
+            },
+            Call::NotifyPointerMotion { dx: 10.0, dy: -5.0 },
+            Call::NotifyPointerAxis {
+                dx: 0.0,
+                dy: 15.0,
+                finish: false,
+            },
+        ]
+    );
+}
+
+#[test]
+fn pointer_scrolls_discretely_and_finishes() {
+    let (_server, portal, whydotool) = connect(PortalConfig::default());
+
+    let pointer = whydotool.virtual_pointer().unwrap();
+    pointer.scroll_discrete(-2, 3).unwrap();
+    pointer
+        .scroll_source(0.0, 20.0, ScrollSource::Finger)
+        .unwrap();
+
+    assert_eq!(
+        portal.calls()[4..],
+        [
+            Call::NotifyPointerAxisDiscrete { axis: 0, steps: 3 },
+            Call::NotifyPointerAxisDiscrete { axis: 1, steps: -2 },
+            Call::NotifyPointerAxis {
+                dx: 0.0,
+                dy: 20.0,
+                finish: true,
+            },
+        ]
+    );
+}
+
+#[test]
+fn output_needs_a_shared_stream() {
+    let (portal, bus) = MockPortal::start(PortalConfig::default());
+    let (_server, conn) = TestServer::start(ServerConfig {
+        outputs: vec![
+            OutputConfig {
+                x: 0,
+                y: 0,
+                width: 1920,
+                height: 1080,
+                ..OutputConfig::default()
+            },
+            OutputConfig {
+                x: 1920,
+                y: 0,
+                width: 1280,
+                height: 1024,
+                ..OutputConfig::default()
+            },
+        ],
+        ..ServerConfig::default()
+    });
+    let mut whydotool = Whydotool::try_from_connection(&conn).unwrap();
+    whydotool.force_portal(true);
+    whydotool.portal_connection(bus);
+    whydotool.restore_tokens(None);
+    whydotool.share_all_outputs(true);
+
+    // Only the first output is shared by the mock portal
+    let pointer = whydotool.virtual_pointer().unwrap();
+    assert_eq!(portal.calls()[2], Call::SelectSources { multiple: true });
+    assert!(matches!(
+        pointer.motion_absolute_output("TEST-1", 0, 0),
+        Err(Error::NoStream)
+    ));
+    assert!(matches!(
+        pointer.motion_absolute_output("DP-9", 0, 0),
+        Err(Error::UnknownOutput(_))
+    ));
+    assert!(matches!(
+        pointer.motion_absolute(2000, 0),
+        Err(Error::NoStream)
+    ));
+    assert_eq!(pointer.absolute_size(None).unwrap(), (3200, 1080));
+    assert_eq!(pointer.absolute_size(Some("TEST-0")).unwrap(), (1920, 1080));
+    assert!(matches!(
+        pointer.absolute_size(Some("TEST-1")),
+        Err(Error::NoStream)
+    ));
+    assert!(
+        !portal
+            .calls()
+            .iter()
+            .any(|call| matches!(call, Call::NotifyPointerMotionAbsolute { .. }))
+    );
+
+    // Positions span every output, like on the other devices
+    pointer.motion_absolute(100, 200).unwrap();
+    assert!(portal.calls().contains(&Call::NotifyPointerMotionAbsolute {
+        stream: 42,
+        x: 100.0,
+        y: 200.0,
+    }));
+}
+
+#[test]
+fn touch_moves_fingers_on_the_stream() {
+    let (_server, portal, whydotool) = connect(PortalConfig {
+        device_types: 7,
+        ..PortalConfig::default()
+    });
+
+    let touch = whydotool.virtual_touch().unwrap();
+    assert_eq!(touch.size(None).unwrap(), (1920, 1080));
+
+    // Pinching out from the center
+    SmoothMotion::new(Duration::ZERO)
+        .steps(2)
+        .touch(
+            touch.as_ref(),
+            None,
+            &[(950.0, 540.0), (970.0, 540.0)],
+            &[(760.0, 540.0), (1160.0, 540.0)],
+            || Ok(()),
+        )
+        .unwrap();
+
+    let calls: Vec<_> = portal
+        .calls()
+        .into_iter()
+        .filter(|call| {
+            matches!(
+                call,
+                Call::SelectDevices { .. }
+                    | Call::NotifyTouchDown { .. }
+                    | Call::NotifyTouchMotion { .. }
+                    | Call::NotifyTouchUp { .. }
+            )
+        })
+        .collect();
+    let down = |slot, x, y| Call::NotifyTouchDown {
+        stream: 42,
+        slot,
+        x,
+        y,
+    };
+    let motion = |slot, x, y| Call::NotifyTouchMotion {
+        stream: 42,
+        slot,
+        x,
+        y,
+    };
+    assert_eq!(
+        calls,
+        [
+            Call::SelectDevices {
+                types: 4,
+                persist_mode: 0,
+                restore_token: None,
+            },
+            down(0, 950.0, 540.0),
+            down(1, 970.0, 540.0),
+            motion(0, 855.0, 540.0),
+            motion(1, 1065.0, 540.0),
+            motion(0, 760.0, 540.0),
+            motion(1, 1160.0, 540.0),
+            Call::NotifyTouchUp { slot: 0 },
+            Call::NotifyTouchUp { slot: 1 },
+        ]
+    );
+
+    // The stream is consumed once for the device, not for every touch
+    assert_eq!(
+        portal
+            .calls()
+            .iter()
+            .filter(|call| **call == Call::OpenPipeWireRemote)
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn touchscreen_needs_support() {
+    let (_server, _portal, whydotool) = connect(PortalConfig::default());
+
+    assert!(matches!(
+        whydotool.virtual_touch(),
+        Err(Error::PortalDeviceUnsupported(_))
+    ));
+}
+
+/// Requests on the EIS server, leaving out frames
+fn eis_requests(portal: &MockPortal) -> Vec<EisRequest> {
+    portal
+        .eis_requests()
+        .into_iter()
+        .filter(|request| *request != EisRequest::Frame)
+        .collect()
+}
+
+#[test]
+fn eis_is_preferred_for_the_pointer() {
+    let (_server, portal, whydotool) = connect(PortalConfig {
+        eis: true,
+        ..PortalConfig::default()
+    });
+
+    let pointer = whydotool.virtual_pointer().unwrap();
+    pointer.button(BTN_LEFT, ButtonState::Pressed).unwrap();
+    pointer.button(BTN_LEFT, ButtonState::Released).unwrap();
+    pointer.motion(5.0, -3.0).unwrap();
+    pointer.motion_absolute(100, 200).unwrap();
+    pointer.scroll_discrete(-2, 3).unwrap();
+    pointer
+        .scroll_source(0.0, 20.0, ScrollSource::Finger)
+        .unwrap();
+    assert_eq!(pointer.absolute_size(None).unwrap(), (1920, 1080));
+    drop(pointer);
+
+    assert_eq!(portal.calls()[4..], [Call::ConnectToEis]);
+    assert_eq!(
+        eis_requests(&portal),
+        [
+            EisRequest::StartEmulating,
+            EisRequest::Button {
+                button: BTN_LEFT,
+                pressed: true,
+            },
+            EisRequest::Button {
+                button: BTN_LEFT,
+                pressed: false,
+            },
+            EisRequest::MotionRelative { dx: 5.0, dy: -3.0 },
+            EisRequest::MotionAbsolute { x: 100.0, y: 200.0 },
+            EisRequest::ScrollDiscrete { dx: -240, dy: 360 },
+            EisRequest::Scroll { dx: 0.0, dy: 20.0 },
+            EisRequest::ScrollStop {
+                x: false,
+                y: true,
+                cancel: false,
+            },
+            EisRequest::StopEmulating,
+        ]
+    );
+
+    // Every request is a frame of its own
+    let frames = portal
+        .eis_requests()
+        .iter()
+        .filter(|request| **request == EisRequest::Frame)
+        .count();
+    assert_eq!(frames, 7);
+}
+
+#[test]
+fn failed_eis_connections_dont_fall_back() {
+    let (_server, portal, whydotool) = connect(PortalConfig {
+        eis: true,
+        eis_seat: false,
+        ..PortalConfig::default()
+    });
+
+    // The portal refuses its own calls once the session was connected to EIS
+    assert!(matches!(whydotool.virtual_pointer(), Err(Error::Eis(_))));
+    assert_eq!(portal.calls()[4..], [Call::ConnectToEis]);
+}
+
+#[test]
+fn eis_keyboard_sends_evdev_codes() {
+    let (_server, portal, whydotool) = connect(PortalConfig {
+        eis: true,
+        ..PortalConfig::default()
+    });
+
+    let mut keyboard = whydotool.virtual_keyboard().unwrap();
+    let tokens = [TextToken::Char('A')];
+    type_tokens(keyboard.as_mut(), &tokens, Duration::ZERO, || Ok(())).unwrap();
+    drop(keyboard);
+
+    let key = |key: i32, pressed| EisRequest::Key {
+        key: key as u32,
+        pressed,
+    };
+    assert_eq!(
+        eis_requests(&portal),
+        [
+            EisRequest::StartEmulating,
+            key(KEY_LEFTSHIFT, true),
+            key(KEY_A, true),
+            key(KEY_A, false),
+            key(KEY_LEFTSHIFT, false),
+            EisRequest::StopEmulating,
+        ]
+    );
+    assert!(
+        !portal
+            .calls()
+            .iter()
+            .any(|call| matches!(call, Call::NotifyKeyboardKeycode { .. }))
+    );
+}
+
+#[test]
+fn eis_touches_within_the_output() {
+    let (portal, bus) = MockPortal::start(PortalConfig {
+        device_types: 7,
+        eis: true,
+        ..PortalConfig::default()
+    });
+    let (_server, conn) = TestServer::start(ServerConfig {
+        outputs: vec![
+            OutputConfig::default(),
+            OutputConfig {
+                x: 1920,
+                y: 0,
+                width: 1280,
+                height: 1024,
+                ..OutputConfig::default()
+            },
+        ],
+        ..ServerConfig::default()
+    });
+    let mut whydotool = Whydotool::try_from_connection(&conn).unwrap();
+    whydotool.portal_connection(bus);
+    whydotool.restore_tokens(None);
+
+    let touch = whydotool.virtual_touch().unwrap();
+    assert_eq!(touch.size(Some("TEST-1")).unwrap(), (1280, 1024));
+    touch.down(Some("TEST-1"), 0, 10.0, 20.0).unwrap();
+    touch.motion(Some("TEST-1"), 0, 15.0, 20.0).unwrap();
+    touch.up(0).unwrap();
+    touch.down(None, 1, 10.0, 20.0).unwrap();
+    touch.up(1).unwrap();
+    assert!(matches!(
+        touch.down(Some("DP-9"), 0, 0.0, 0.0),
+        Err(Error::UnknownOutput(_))
+    ));
+    drop(touch);
+
+    assert_eq!(
+        eis_requests(&portal),
+        [
+            EisRequest::StartEmulating,
+            EisRequest::TouchDown {
+                id: 0,
+                x: 1930.0,
+                y: 20.0,
+            },
+            EisRequest::TouchMotion {
+                id: 0,
+                x: 1935.0,
+                y: 20.0,
+            },
+            EisRequest::TouchUp { id: 0 },
+            EisRequest::TouchDown {
+                id: 1,
+                x: 10.0,
+                y: 20.0,
+            },
+            EisRequest::TouchUp { id: 1 },
+            EisRequest::StopEmulating,
+        ]
+    );
+}
+
+#[test]
+fn without_eis_the_portal_is_notified() {
+    let (_server, portal, whydotool) = connect(PortalConfig::default());
+
+    let pointer = whydotool.virtual_pointer().unwrap();
+    pointer.motion(1.0, 1.0).unwrap();
+
+    assert!(portal.eis_requests().is_empty());
+    assert_eq!(
+        portal.calls()[4..],
+        [Call::NotifyPointerMotion { dx: 1.0, dy: 1.0 }]
+    );
+}
+
+#[test]
+fn unsupported_devices_are_not_selected() {
+    let (_server, portal, whydotool) = connect(PortalConfig {
+        device_types: 2,
+        ..PortalConfig::default()
+    });
+
+    // No session is started without a device to select
+    assert!(matches!(
+        whydotool.virtual_keyboard(),
+        Err(Error::PortalDeviceUnsupported(_))
+    ));
+    assert!(portal.calls().is_empty());
+}
+
+#[test]
+fn delayed_response_is_awaited() {
+    let (_server, portal, whydotool) = connect(PortalConfig {
+        replies: vec![Reply::Delay(Duration::from_millis(200))],
+        ..PortalConfig::default()
+    });
+
+    assert!(whydotool.virtual_keyboard().is_ok());
+    assert_eq!(portal.calls().last(), Some(&Call::Start));
+}
+
+#[test]
+fn cancelled_session() {
+    let (_server, portal, whydotool) = connect(PortalConfig {
+        replies: vec![Reply::Cancel],
+        ..PortalConfig::default()
+    });
+
+    assert!(matches!(
+        whydotool.virtual_keyboard(),
+        Err(Error::PortalRejected)
+    ));
+    assert_eq!(portal.calls(), [Call::CreateSession]);
+}
+
+#[test]
+fn rejected_start() {
+    let (_server, portal, whydotool) = connect(PortalConfig {
+        replies: vec![Reply::Accept, Reply::Accept, Reply::Accept, Reply::Reject],
+        ..PortalConfig::default()
+    });
+
+    assert!(matches!(
+        whydotool.virtual_pointer(),
+        Err(Error::PortalRejected)
+    ));
+    assert_eq!(portal.calls().last(), Some(&Call::Start));
+}
+
+/// Restore token sent with the session's `SelectDevices` call
+fn sent_restore_token(portal: &MockPortal) -> Option<String> {
+    portal.calls().into_iter().find_map(|call| match call {
+        Call::SelectDevices {
+            persist_mode,
+            restore_token,
+            ..
+        } => {
+            assert_eq!(persist_mode, 2);
+            restore_token
+        }
+        _ => None,
+    })
+}
+
+#[test]
+fn restore_token_is_reused() {
+    let state_dir = tempfile::tempdir().unwrap();
+    let restore_tokens = RestoreTokens::new(state_dir.path().to_path_buf());
+
+    let mut sent = Vec::new();
+    for forget in [false, false, true] {
+        if forget {
+            restore_tokens.forget().unwrap();
+        }
+
+        let (_server, portal, mut whydotool) = connect(PortalConfig::default());
+        whydotool.restore_tokens(Some(restore_tokens.clone()));
+        whydotool.virtual_keyboard().unwrap();
+
+        sent.push(sent_restore_token(&portal));
+    }
+
+    // Each mock portal issues token-1 for its first session
+    assert_eq!(sent, [None, Some("token-1".to_string()), None]);
+    assert_eq!(restore_tokens.load("keyboard").as_deref(), Some("token-1"));
+}
+
+#[test]
+fn restore_tokens_are_per_device_set() {
+    let state_dir = tempfile::tempdir().unwrap();
+    let restore_tokens = RestoreTokens::new(state_dir.path().to_path_buf());
+    restore_tokens.store("keyboard", "keyboard-token").unwrap();
+
+    let (_server, portal, mut whydotool) = connect(PortalConfig::default());
+    whydotool.restore_tokens(Some(restore_tokens.clone()));
+    whydotool.virtual_pointer().unwrap();
+
+    assert_eq!(sent_restore_token(&portal), None);
+    assert_eq!(
+        restore_tokens.load("pointer-screencast").as_deref(),
+        Some("token-1")
+    );
+    assert_eq!(
+        restore_tokens.load("keyboard").as_deref(),
+        Some("keyboard-token")
+    );
+}
+
+#[test]
+fn restore_tokens_are_private() {
+    let state_dir = tempfile::tempdir().unwrap();
+    let dir = state_dir.path().join("whydotool");
+    let restore_tokens = RestoreTokens::new(dir.clone());
+    restore_tokens.store("keyboard", "keyboard-token").unwrap();
+
+    let mode = |path: &std::path::Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(&dir), 0o700);
+    assert_eq!(mode(&dir.join("keyboard.token")), 0o600);
+
+    // Tokens stored before with looser permissions are tightened on the next store
+    fs::set_permissions(
+        dir.join("keyboard.token"),
+        fs::Permissions::from_mode(0o644),
+    )
+    .unwrap();
+    restore_tokens.store("keyboard", "new").unwrap();
+    assert_eq!(mode(&dir.join("keyboard.token")), 0o600);
+    assert_eq!(restore_tokens.load("keyboard").as_deref(), Some("new"));
+}
@@ -0,0 +1,300 @@
+mod common;
+
+use common::{
+    key,
+    server::{Request, ServerConfig, TestServer, whydotool_command},
+    timed,
+};
+use libwhydotool::{EvdevDecoder, Event, to_script};
+use nix::libc;
+use std::{fs, mem};
+use wayland_server::protocol::wl_pointer;
+
+const KEY_ESC: u32 = 1;
+const KEY_C: u32 = 46;
+const KEY_ENTER: u32 = 28;
+const KEY_LEFTCTRL: u32 = 29;
+const KEY_A: u32 = 30;
+const BTN_LEFT: u32 = 0x110;
+
+// Event types and codes from `linux/input-event-codes.h`
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const EV_ABS: u16 = 0x03;
+const BTN_TOUCH: u16 = 0x14a;
+const BTN_TOOL_FINGER: u16 = 0x145;
+
+/// `struct input_event`s as read from an evdev device, with zeroed timestamps
+fn input_events(events: &[(u16, u16, i32)]) -> Vec<u8> {
+    let size = mem::size_of::<libc::input_event>();
+    events
+        .iter()
+        .flat_map(|(kind, code, value)| {
+            let mut raw = vec![0; size - 8];
+            raw.extend(kind.to_ne_bytes());
+            raw.extend(code.to_ne_bytes());
+            raw.extend(value.to_ne_bytes());
+            raw
+        })
+        .collect()
+}
+
+const fn report() -> (u16, u16, i32) {
+    (EV_SYN, 0, 0)
+}
+
+#[test]
+fn keys_are_named_and_timed() {
+    let events = timed(&[
+        // Return that started the recording
+        (0, key(KEY_ENTER, false)),
+        (5, key(KEY_A, true)),
+        (55, key(KEY_A, false)),
+        (300, key(250, true)),
+        (310, key(250, false)),
+        // Ctrl+C that stopped it
+        (900, key(KEY_LEFTCTRL, true)),
+        (950, key(KEY_C, true)),
+    ]);
+
+    assert_eq!(
+        to_script(&events),
+        "key -d 0 KEY_A:1\nsleep 50\nkey -d 0 KEY_A:0\nsleep 245\nkey -d 0 250:1\nsleep 10\nkey -d 0 250:0\n"
+    );
+}
+
+#[test]
+fn pointer_motion_is_merged() {
+    let events = timed(&[
+        (0, Event::Motion { x: 1.0, y: 2.0 }),
+        (4, Event::Motion { x: 3.0, y: -1.0 }),
+        (8, Event::Scroll { x: 0.0, y: 15.0 }),
+        (20, Event::Motion { x: -2.0, y: 0.0 }),
+        (
+            30,
+            Event::Button {
+                button: BTN_LEFT + 1,
+                pressed: true,
+            },
+        ),
+        (
+            80,
+            Event::Button {
+                button: BTN_LEFT + 1,
+                pressed: false,
+            },
+        ),
+        // Beyond the buttons a click byte can address, kept by its code
+        (
+            90,
+            Event::Button {
+                button: 0x140,
+                pressed: true,
+            },
+        ),
+    ]);
+
+    assert_eq!(
+        to_script(&events),
+        "mousemove -x 4 -y 1\nsleep 8\nmousemove -w -x 0 -y 15\nsleep 12\nmousemove -x -2 -y 0\nsleep 10\nclick right:down\nsleep 50\nclick right:up\nsleep 10\nclick 0x140:down\n"
+    );
+}
+
+#[test]
+fn recorded_script_replays() {
+    let events = timed(&[
+        (0, key(KEY_ESC, true)),
+        (1, key(KEY_ESC, false)),
+        (
+            2,
+            Event::Button {
+                button: BTN_LEFT,
+                pressed: true,
+            },
+        ),
+        (
+            3,
+            Event::Button {
+                button: BTN_LEFT,
+                pressed: false,
+            },
+        ),
+        (4, Event::Scroll { x: 0.0, y: -15.0 }),
+    ]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("recorded.wdt");
+    fs::write(&path, to_script(&events)).unwrap();
+
+    let (server, socket) = TestServer::start_socket(ServerConfig::default());
+    let output = whydotool_command(&socket)
+        .arg("run")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let requests: Vec<_> = server
+        .requests()
+        .into_iter()
+        .filter(|request| {
+            matches!(
+                request,
+                Request::Key { .. } | Request::Button { .. } | Request::Axis { .. }
+            )
+        })
+        .collect();
+    assert_eq!(
+        requests,
+        [
+            Request::Key {
+                key: KEY_ESC,
+                state: 1
+            },
+            Request::Key {
+                key: KEY_ESC,
+                state: 0
+            },
+            Request::Button {
+                button: BTN_LEFT,
+                pressed: true
+            },
+            Request::Button {
+                button: BTN_LEFT,
+                pressed: false
+            },
+            Request::Axis {
+                axis: wl_pointer::Axis::VerticalScroll,
+                value: -15.0
+            },
+        ]
+    );
+}
+
+#[test]
+fn evdev_keys_buttons_and_wheels() {
+    let mut decoder = EvdevDecoder::new();
+    let events = decoder.decode(&input_events(&[
+        (EV_KEY, KEY_A as u16, 1),
+        // Autorepeat isn't recorded
+        (EV_KEY, KEY_A as u16, 2),
+        (EV_KEY, BTN_LEFT as u16, 1),
+        (EV_REL, 0, 3),
+        (EV_REL, 1, -4),
+        (EV_REL, 0, 1),
+        (EV_REL, 8, 1),
+        report(),
+        (EV_KEY, KEY_A as u16, 0),
+        report(),
+    ]));
+
+    assert_eq!(
+        events,
+        [
+            key(KEY_A, true),
+            Event::Button {
+                button: BTN_LEFT,
+                pressed: true,
+            },
+            Event::Motion { x: 4.0, y: -4.0 },
+            Event::Scroll { x: 0.0, y: -15.0 },
+            key(KEY_A, false),
+        ]
+    );
+}
+
+#[test]
+fn evdev_keys_above_buttons() {
+    const KEY_OK: u16 = 0x160;
+    const BTN_SOUTH: u16 = 0x130;
+    const BTN_TRIGGER_HAPPY1: u16 = 0x2c0;
+
+    let mut decoder = EvdevDecoder::new();
+    let events = decoder.decode(&input_events(&[
+        (EV_KEY, KEY_OK, 1),
+        (EV_KEY, BTN_SOUTH, 1),
+        (EV_KEY, BTN_TRIGGER_HAPPY1, 1),
+        (EV_KEY, KEY_OK, 0),
+        report(),
+    ]));
+
+    assert_eq!(
+        events,
+        [key(KEY_OK.into(), true), key(KEY_OK.into(), false)]
+    );
+}
+
+#[test]
+fn evdev_absolute_devices_arent_touchpads() {
+    // Like a tablet or touchscreen, which report positions without a finger tool
+    let mut decoder = EvdevDecoder::new();
+    let events = decoder.decode(&input_events(&[
+        (EV_KEY, BTN_TOUCH, 1),
+        (EV_ABS, 0, 1000),
+        (EV_ABS, 1, 500),
+        report(),
+        (EV_ABS, 0, 1010),
+        report(),
+    ]));
+    assert!(events.is_empty());
+
+    // Once a finger is reported it's a touchpad
+    let events = decoder.decode(&input_events(&[
+        (EV_KEY, BTN_TOOL_FINGER, 1),
+        (EV_ABS, 0, 1020),
+        (EV_ABS, 1, 500),
+        report(),
+        (EV_ABS, 0, 1030),
+        report(),
+    ]));
+    assert_eq!(events, [Event::Motion { x: 10.0, y: 0.0 }]);
+}
+
+#[test]
+fn evdev_touchpad_motion_is_relative() {
+    let mut decoder = EvdevDecoder::new().touchpad(true);
+    let mut events = decoder.decode(&input_events(&[
+        // Touching doesn't move the pointer, moving the finger does
+        (EV_KEY, BTN_TOUCH, 1),
+        (EV_ABS, 0, 1000),
+        (EV_ABS, 1, 500),
+        report(),
+        (EV_ABS, 0, 1010),
+        report(),
+        (EV_ABS, 1, 490),
+        report(),
+        (EV_KEY, BTN_TOUCH, 0),
+        report(),
+    ]));
+
+    // Touching again elsewhere doesn't jump
+    events.extend(decoder.decode(&input_events(&[
+        (EV_KEY, BTN_TOUCH, 1),
+        (EV_ABS, 0, 100),
+        (EV_ABS, 1, 100),
+        report(),
+        (EV_ABS, 0, 95),
+        (EV_ABS, 1, 103),
+        report(),
+    ])));
+
+    assert_eq!(
+        events,
+        [
+            Event::Motion { x: 10.0, y: 0.0 },
+            Event::Motion { x: 0.0, y: -10.0 },
+            Event::Motion { x: -5.0, y: 3.0 },
+        ]
+    );
+
+    // Without a finger down, like a joystick, positions aren't motion
+    let events = decoder.decode(&input_events(&[
+        (EV_KEY, BTN_TOUCH, 0),
+        (EV_ABS, 0, 0),
+        report(),
+        (EV_ABS, 0, 50),
+        report(),
+    ]));
+    assert!(events.is_empty());
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use common::server::{Request, ServerConfig, TestServer, whydotool_command};
+use std::{
+    fs,
+    process::Output,
+    time::{Duration, Instant},
+};
+
+const KEY_ENTER: u32 = 28;
+const KEY_A: u32 = 30;
+const KEY_LEFTSHIFT: u32 = 42;
+const KEY_B: u32 = 48;
+const KEY_SPACE: u32 = 57;
+const BTN_RIGHT: u32 = 0x111;
+
+fn run_script(script: &str) -> (Output, Vec<Request>) {
+    run_script_with(script, &[])
+}
+
+fn run_script_with(script: &str, args: &[&str]) -> (Output, Vec<Request>) {
+    let (server, socket) = TestServer::start_socket(ServerConfig::default());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("script.wdt");
+    fs::write(&path, script).unwrap();
+
+    let output = whydotool_command(&socket)
+        .arg("run")
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    // Server side of the connection stays open until the server is dropped
+    let requests = server.requests();
+    (output, requests)
+}
+
+fn keys(requests: &[Request]) -> Vec<(u32, u32)> {
+    requests
+        .iter()
+        .filter_map(|request| match request {
+            Request::Key { key, state } => Some((*key, *state)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn runs_commands_over_one_connection() {
+    let (output, requests) = run_script(
+        r#"
+# Comments and blank lines are skipped
+
+type -d 0 -H 0 "a B"
+sleep 10
+key -d 0 Return
+click 0xC1
+type -d 0 -H 0 'a\n'
+"#,
+    );
+
+    assert!(output.status.success(), "{output:?}");
+
+    let keymaps = requests
+        .iter()
+        .filter(|request| matches!(request, Request::Keymap { .. }))
+        .count();
+    assert_eq!(keymaps, 1);
+
+    assert_eq!(
+        keys(&requests),
+        [
+            (KEY_A, 1),
+            (KEY_A, 0),
+            (KEY_SPACE, 1),
+            (KEY_SPACE, 0),
+            (KEY_LEFTSHIFT, 1),
+            (KEY_B, 1),
+            (KEY_B, 0),
+            (KEY_LEFTSHIFT, 0),
+            (KEY_ENTER, 1),
+            (KEY_ENTER, 0),
+            (KEY_A, 1),
+            (KEY_A, 0),
+            (KEY_ENTER, 1),
+            (KEY_ENTER, 0),
+        ]
+    );
+    assert!(requests.contains(&Request::Button {
+        button: BTN_RIGHT,
+        pressed: true,
+    }));
+}
+
+#[test]
+fn invalid_line_runs_nothing() {
+    let (output, requests) = run_script("type -d 0 -H 0 a\nsleep soon\n");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Line 2"), "{stderr}");
+    assert!(keys(&requests).is_empty());
+}
+
+#[test]
+fn nested_commands_are_rejected() {
+    let (output, _) = run_script("stdin\n");
+    assert!(!output.status.success());
+
+    let (output, _) = run_script("type -d 0 \"unterminated\n");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unterminated double quote"));
+}
+
+#[test]
+fn sleeps_wait_after_commands() {
+    let start = Instant::now();
+    let (output, _) = run_script("key -d 300 Return\nsleep 300\n");
+
+    assert!(output.status.success(), "{output:?}");
+    // Time spent running the key command doesn't shorten the sleep after it
+    assert!(start.elapsed() >= Duration::from_millis(900));
+}
+
+#[test]
+fn loops_without_delays() {
+    let start = Instant::now();
+    let (output, requests) = run_script_with(
+        "key -d 1000 Return\nsleep 5000\n",
+        &["--no-delays", "--loop", "3"],
+    );
+
+    assert!(output.status.success(), "{output:?}");
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(keys(&requests), [(KEY_ENTER, 1), (KEY_ENTER, 0)].repeat(3));
+}
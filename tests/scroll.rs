@@ -0,0 +1,135 @@
+mod common;
+
+use common::server::{Request, ServerConfig, TestServer, whydotool_command};
+use libwhydotool::ScrollSource;
+use std::process::Output;
+use wayland_server::protocol::wl_pointer::{Axis, AxisSource};
+
+fn scroll(args: &[&str]) -> (Output, Vec<Request>) {
+    scroll_on(ServerConfig::default(), args)
+}
+
+fn scroll_on(config: ServerConfig, args: &[&str]) -> (Output, Vec<Request>) {
+    let (server, socket) = TestServer::start_socket(config);
+
+    let output = whydotool_command(&socket)
+        .arg("scroll")
+        .args(args)
+        .output()
+        .unwrap();
+
+    let requests = server.requests();
+    (output, requests)
+}
+
+#[test]
+fn parse_sources() {
+    assert_eq!("wheel".parse(), Ok(ScrollSource::Wheel));
+    assert_eq!("finger".parse(), Ok(ScrollSource::Finger));
+    assert_eq!("continuous".parse(), Ok(ScrollSource::Continuous));
+    assert!("touchpad".parse::<ScrollSource>().is_err());
+}
+
+#[test]
+fn clicks_are_discrete() {
+    let (output, requests) = scroll(&["--clicks", "3"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        requests,
+        [
+            Request::AxisSource(AxisSource::Wheel),
+            Request::AxisDiscrete {
+                axis: Axis::VerticalScroll,
+                value: 45.0,
+                discrete: 3,
+            },
+            Request::Frame,
+        ]
+    );
+
+    let (output, requests) = scroll(&["--clicks", "-1", "--horizontal"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(requests.contains(&Request::AxisDiscrete {
+        axis: Axis::HorizontalScroll,
+        value: -15.0,
+        discrete: -1,
+    }));
+}
+
+#[test]
+fn finger_scroll_stops() {
+    let (output, requests) = scroll(&["-y", "-40.5", "--source", "finger"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        requests,
+        [
+            Request::AxisSource(AxisSource::Finger),
+            Request::Axis {
+                axis: Axis::VerticalScroll,
+                value: -40.5,
+            },
+            Request::Frame,
+            Request::AxisSource(AxisSource::Finger),
+            Request::AxisStop(Axis::VerticalScroll),
+            Request::Frame,
+        ]
+    );
+
+    let (output, requests) = scroll(&["-x", "10"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        requests,
+        [
+            Request::AxisSource(AxisSource::Continuous),
+            Request::Axis {
+                axis: Axis::HorizontalScroll,
+                value: 10.0,
+            },
+            Request::Frame,
+        ]
+    );
+}
+
+#[test]
+fn version_1_pointers_only_scroll() {
+    let config = || ServerConfig {
+        virtual_pointer_version: 1,
+        ..ServerConfig::default()
+    };
+
+    let (output, requests) = scroll_on(config(), &["--clicks", "3"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        requests,
+        [
+            Request::Axis {
+                axis: Axis::VerticalScroll,
+                value: 45.0,
+            },
+            Request::Frame,
+        ]
+    );
+
+    let (output, requests) = scroll_on(config(), &["-y", "-40.5", "--source", "finger"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        requests,
+        [
+            Request::Axis {
+                axis: Axis::VerticalScroll,
+                value: -40.5,
+            },
+            Request::Frame,
+        ]
+    );
+}
+
+#[test]
+fn clicks_and_distances_conflict() {
+    let (output, requests) = scroll(&["--clicks", "1", "-y", "15"]);
+    assert!(!output.status.success());
+    assert!(requests.is_empty());
+
+    let (output, _) = scroll(&[]);
+    assert!(!output.status.success());
+}
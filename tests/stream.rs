@@ -0,0 +1,45 @@
+use libwhydotool::TextStream;
+use std::io::{self, Read};
+
+/// Reader handing out `chunks` one per read, like a pipe written to piece by piece
+struct Chunks(Vec<&'static [u8]>);
+
+impl Read for Chunks {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.0.is_empty() {
+            return Ok(0);
+        }
+
+        let chunk = self.0.remove(0);
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        Ok(chunk.len())
+    }
+}
+
+fn stream(chunks: Vec<&'static [u8]>, whole_lines: bool) -> Vec<String> {
+    TextStream::new(Chunks(chunks), whole_lines)
+        .collect::<io::Result<_>>()
+        .unwrap()
+}
+
+#[test]
+fn characters_split_across_reads() {
+    // 'ä' is 0xc3 0xa4, '☃' is 0xe2 0x98 0x83
+    let chunks: Vec<&[u8]> = vec![b"a\xc3", b"\xa4\xe2", b"\x98", b"\x83b"];
+    assert_eq!(stream(chunks, false), ["a", "ä", "☃b"]);
+}
+
+#[test]
+fn lines_split_across_reads() {
+    let chunks: Vec<&[u8]> = vec![b"one\ntw", b"o\nthree\n"];
+    assert_eq!(stream(chunks.clone(), true), ["one\n", "two\n", "three\n"]);
+    assert_eq!(stream(chunks, false), ["one\n", "tw", "o\n", "three\n"]);
+}
+
+#[test]
+fn last_line_without_newline() {
+    assert_eq!(stream(vec![b"one\ntwo"], true), ["one\n", "two"]);
+    assert_eq!(stream(vec![b"one\ntwo"], false), ["one\n", "two"]);
+    // An unfinished character at the end is replaced rather than lost
+    assert_eq!(stream(vec![b"a\xe2\x98"], true), ["a\u{fffd}"]);
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use common::{
+    key,
+    server::{ServerConfig, TestServer, whydotool_command},
+};
+use libwhydotool::{
+    Error, Event, KEY_MAX, Key, TextToken, parse_chord, parse_escapes, type_tokens,
+};
+use std::{
+    io::Write,
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+const KEY_TAB: u32 = 15;
+const KEY_ENTER: u32 = 28;
+const KEY_LEFTCTRL: u32 = 29;
+const KEY_A: u32 = 30;
+const KEY_APOSTROPHE: u32 = 40;
+const KEY_LEFTSHIFT: u32 = 42;
+const KEY_B: u32 = 48;
+
+fn type_text(text: &str, escape: bool) -> (Vec<Event>, usize) {
+    let (whydotool, recorder) = common::whydotool();
+    let mut keyboard = whydotool.virtual_keyboard().unwrap();
+
+    let tokens = if escape {
+        parse_escapes(text)
+    } else {
+        text.chars().map(TextToken::Char).collect()
+    };
+
+    let mut typed = 0;
+    type_tokens(keyboard.as_mut(), &tokens, Duration::ZERO, || {
+        typed += 1;
+        Ok(())
+    })
+    .unwrap();
+    keyboard.restore_keymap().unwrap();
+
+    (common::events(&recorder), typed)
+}
+
+#[test]
+fn plain_and_shifted() {
+    let (events, typed) = type_text("aB", false);
+
+    assert_eq!(typed, 2);
+    assert_eq!(
+        events,
+        [
+            key(KEY_A, true),
+            key(KEY_A, false),
+            key(KEY_LEFTSHIFT, true),
+            key(KEY_B, true),
+            key(KEY_B, false),
+            key(KEY_LEFTSHIFT, false),
+        ]
+    );
+}
+
+#[test]
+fn escapes() {
+    let (events, _) = type_text(r"\n\t\{ctrl+a}", true);
+
+    assert_eq!(
+        events,
+        [
+            key(KEY_ENTER, true),
+            key(KEY_ENTER, false),
+            key(KEY_TAB, true),
+            key(KEY_TAB, false),
+            key(KEY_LEFTCTRL, true),
+            key(KEY_A, true),
+            key(KEY_A, false),
+            key(KEY_LEFTCTRL, false),
+        ]
+    );
+}
+
+#[test]
+fn escapes_disabled() {
+    let (events, typed) = type_text(r"\n", false);
+
+    // Backslash and n, neither needs a modifier on the us layout
+    assert_eq!(typed, 2);
+    assert_eq!(events.len(), 4);
+    assert!(!events.contains(&key(KEY_ENTER, true)));
+}
+
+#[test]
+fn other_layout_is_locked_and_restored() {
+    let (events, _) = type_text("ä", false);
+
+    assert_eq!(
+        events,
+        [
+            Event::Layout(1),
+            key(KEY_APOSTROPHE, true),
+            key(KEY_APOSTROPHE, false),
+            Event::Layout(0),
+        ]
+    );
+}
+
+#[test]
+fn unmapped_chars_get_a_keymap() {
+    let (events, typed) = type_text("☃a☃", false);
+
+    assert_eq!(typed, 3);
+    assert_eq!(events.first(), Some(&Event::Keymap));
+    assert_eq!(events.last(), Some(&Event::Keymap));
+    // Both snowmen use the same key and the keymap is only replaced once
+    assert_eq!(events.iter().filter(|e| **e == Event::Keymap).count(), 2);
+    assert_eq!(events[1], events[5]);
+    assert_eq!(&events[3..5], [key(KEY_A, true), key(KEY_A, false)]);
+}
+
+#[test]
+fn keycodes_above_key_max() {
+    assert_eq!(parse_chord("767"), Ok(vec![Key::Code(KEY_MAX)]));
+    assert!(parse_chord("768").is_err());
+    assert!(parse_chord("ctrl+4294967295").is_err());
+}
+
+#[test]
+fn unknown_key_in_chord() {
+    let (whydotool, recorder) = common::whydotool();
+    let mut keyboard = whydotool.virtual_keyboard().unwrap();
+
+    let result = type_tokens(
+        keyboard.as_mut(),
+        &parse_escapes(r"\{ctrl+nosuchkey}"),
+        Duration::ZERO,
+        || Ok(()),
+    );
+
+    assert!(matches!(result, Err(Error::UnknownKey(_))));
+    assert!(recorder.events().is_empty());
+}
+
+#[test]
+fn next_delay_between_streamed_lines() {
+    let (_server, socket) = TestServer::start_socket(ServerConfig::default());
+    let mut child = whydotool_command(&socket)
+        .args(["type", "-d", "0", "-H", "0", "-D", "100", "-f", "-"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"a\nb\nc").unwrap();
+
+    let start = Instant::now();
+    assert!(child.wait().unwrap().success());
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
@@ -0,0 +1,408 @@
+mod common;
+
+use common::server::{OutputConfig, Request, ServerConfig, TestServer};
+use libwhydotool::{Error, PointerPosition, TextToken, Whydotool, type_tokens};
+use std::time::Duration;
+use wayland_client::protocol::wl_pointer::ButtonState;
+use wayland_server::protocol::{wl_output, wl_pointer::Axis};
+
+const KEY_A: u32 = 30;
+const KEY_APOSTROPHE: u32 = 40;
+const KEY_LEFTSHIFT: u32 = 42;
+const BTN_LEFT: u32 = 0x110;
+// Keymap format of wl_keyboard
+const XKB_V1: u32 = 1;
+const SHIFT: u32 = 1;
+
+fn connect(config: ServerConfig) -> (TestServer, Whydotool) {
+    let (server, conn) = TestServer::start(config);
+    let whydotool = Whydotool::try_from_connection(&conn).unwrap();
+    (server, whydotool)
+}
+
+fn key(key: u32, pressed: bool) -> Request {
+    Request::Key {
+        key,
+        state: u32::from(pressed),
+    }
+}
+
+const fn modifiers(depressed: u32, group: u32) -> Request {
+    Request::Modifiers {
+        depressed,
+        latched: 0,
+        locked: 0,
+        group,
+    }
+}
+
+fn type_text(whydotool: &mut Whydotool, text: &str) {
+    let mut keyboard = whydotool.virtual_keyboard().unwrap();
+    let tokens = text.chars().map(TextToken::Char).collect::<Vec<_>>();
+    type_tokens(keyboard.as_mut(), &tokens, Duration::ZERO, || Ok(())).unwrap();
+    keyboard.restore_keymap().unwrap();
+    whydotool.roundtrip().unwrap();
+}
+
+#[test]
+fn repeat_rate_sets_key_delay() {
+    let (_server, whydotool) = connect(ServerConfig {
+        repeat_rate: 25,
+        ..ServerConfig::default()
+    });
+
+    assert_eq!(whydotool.key_delay(), 40);
+}
+
+#[test]
+fn keyboard_uploads_seat_keymap() {
+    let config = ServerConfig::default();
+    let keymap = config.keymap.clone();
+    let (server, mut whydotool) = connect(config);
+
+    let _keyboard = whydotool.virtual_keyboard().unwrap();
+    whydotool.roundtrip().unwrap();
+
+    assert_eq!(
+        server.requests(),
+        [Request::Keymap {
+            format: XKB_V1,
+            keymap,
+        }]
+    );
+}
+
+#[test]
+fn shifted_char_serializes_modifiers() {
+    let (server, mut whydotool) = connect(ServerConfig::default());
+
+    type_text(&mut whydotool, "A");
+
+    assert_eq!(
+        server.requests()[1..],
+        [
+            key(KEY_LEFTSHIFT, true),
+            modifiers(SHIFT, 0),
+            key(KEY_A, true),
+            modifiers(SHIFT, 0),
+            key(KEY_A, false),
+            modifiers(SHIFT, 0),
+            key(KEY_LEFTSHIFT, false),
+            modifiers(0, 0),
+        ]
+    );
+}
+
+#[test]
+fn other_layout_locks_group() {
+    let (server, mut whydotool) = connect(ServerConfig::default());
+
+    type_text(&mut whydotool, "ä");
+
+    assert_eq!(
+        server.requests()[1..],
+        [
+            modifiers(0, 1),
+            key(KEY_APOSTROPHE, true),
+            modifiers(0, 1),
+            key(KEY_APOSTROPHE, false),
+            modifiers(0, 1),
+            modifiers(0, 0),
+        ]
+    );
+}
+
+#[test]
+fn unmapped_char_uploads_augmented_keymap() {
+    let config = ServerConfig::default();
+    let original = config.keymap.clone();
+    let (server, mut whydotool) = connect(config);
+
+    type_text(&mut whydotool, "☃");
+
+    let keymaps = server
+        .requests()
+        .into_iter()
+        .filter_map(|request| match request {
+            Request::Keymap { keymap, .. } => Some(keymap),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(keymaps.len(), 3);
+    assert_eq!(keymaps[0], original);
+    assert!(keymaps[1].contains("[ U2603 ]"));
+    assert_eq!(keymaps[2], original);
+}
+
+#[test]
+fn motion_absolute_spans_all_outputs() {
+    let (server, mut whydotool) = connect(ServerConfig {
+        outputs: vec![
+            OutputConfig {
+                x: 0,
+                y: 0,
+                width: 1920,
+                height: 1080,
+                ..OutputConfig::default()
+            },
+            OutputConfig {
+                x: 1920,
+                y: 0,
+                width: 1280,
+                height: 1024,
+                ..OutputConfig::default()
+            },
+        ],
+        ..ServerConfig::default()
+    });
+
+    let pointer = whydotool.virtual_pointer().unwrap();
+    pointer.motion_absolute(2000, 500).unwrap();
+    whydotool.roundtrip().unwrap();
+
+    assert_eq!(
+        server.requests(),
+        [
+            Request::MotionAbsolute {
+                x: 2000,
+                y: 500,
+                x_extent: 3200,
+                y_extent: 1080,
+            },
+            Request::Frame,
+        ]
+    );
+}
+
+#[test]
+fn motion_absolute_on_named_output() {
+    let (server, mut whydotool) = connect(ServerConfig {
+        outputs: vec![
+            OutputConfig {
+                x: 0,
+                y: 0,
+                width: 1920,
+                height: 1080,
+                ..OutputConfig::default()
+            },
+            OutputConfig {
+                x: 1920,
+                y: 0,
+                width: 1280,
+                height: 1024,
+                ..OutputConfig::default()
+            },
+        ],
+        ..ServerConfig::default()
+    });
+
+    let pointer = whydotool.virtual_pointer().unwrap();
+    pointer.motion_absolute_output("TEST-1", 640, 512).unwrap();
+    assert!(matches!(
+        pointer.motion_absolute_output("DP-9", 0, 0),
+        Err(Error::UnknownOutput(name)) if &*name == "DP-9"
+    ));
+    whydotool.roundtrip().unwrap();
+
+    assert_eq!(
+        server.requests(),
+        [
+            Request::MotionAbsolute {
+                x: 2560,
+                y: 512,
+                x_extent: 3200,
+                y_extent: 1080,
+            },
+            Request::Frame,
+        ]
+    );
+}
+
+/// Extents of the absolute motion to `output`'s top left corner
+fn output_extents(config: ServerConfig, output: &str) -> (u32, u32, u32, u32) {
+    let (server, mut whydotool) = connect(config);
+
+    let pointer = whydotool.virtual_pointer().unwrap();
+    pointer.motion_absolute_output(output, 0, 0).unwrap();
+    whydotool.roundtrip().unwrap();
+
+    match server.requests().first() {
+        Some(Request::MotionAbsolute {
+            x,
+            y,
+            x_extent,
+            y_extent,
+        }) => (*x, *y, *x_extent, *y_extent),
+        requests => panic!("{requests:?}"),
+    }
+}
+
+#[test]
+fn fractional_scale_uses_xdg_output() {
+    let config = ServerConfig {
+        outputs: vec![
+            OutputConfig {
+                scale: 1.5,
+                ..OutputConfig::default()
+            },
+            OutputConfig {
+                x: 1920,
+                y: 0,
+                width: 1280,
+                height: 1024,
+                scale: 1.25,
+                ..OutputConfig::default()
+            },
+        ],
+        ..ServerConfig::default()
+    };
+
+    assert_eq!(output_extents(config, "TEST-1"), (1920, 0, 3200, 1080));
+}
+
+#[test]
+fn scale_and_transform_without_xdg_output() {
+    let config = ServerConfig {
+        outputs: vec![
+            OutputConfig {
+                scale: 2.0,
+                ..OutputConfig::default()
+            },
+            OutputConfig {
+                x: 1920,
+                y: 0,
+                width: 1024,
+                height: 1280,
+                transform: wl_output::Transform::_90,
+                ..OutputConfig::default()
+            },
+        ],
+        xdg_output: false,
+        ..ServerConfig::default()
+    };
+
+    assert_eq!(output_extents(config, "TEST-1"), (1920, 0, 2944, 1280));
+}
+
+#[test]
+fn outputs_left_of_and_above_the_origin() {
+    let config = || ServerConfig {
+        outputs: vec![
+            OutputConfig::default(),
+            OutputConfig {
+                x: -1280,
+                y: -200,
+                width: 1280,
+                height: 1024,
+                ..OutputConfig::default()
+            },
+        ],
+        ..ServerConfig::default()
+    };
+
+    // Positions are relative to the top left corner of everything, which the left output is at
+    assert_eq!(output_extents(config(), "TEST-1"), (0, 0, 3200, 1280));
+    assert_eq!(output_extents(config(), "TEST-0"), (1280, 200, 3200, 1280));
+}
+
+#[test]
+fn pointer_position_from_entered_output() {
+    let (_server, mut whydotool) = connect(ServerConfig {
+        outputs: vec![
+            OutputConfig::default(),
+            OutputConfig {
+                x: 1920,
+                y: 0,
+                width: 1280,
+                height: 1024,
+                ..OutputConfig::default()
+            },
+        ],
+        pointer: (2000.0, 300.5),
+        ..ServerConfig::default()
+    });
+
+    assert_eq!(
+        whydotool.pointer_position().unwrap(),
+        PointerPosition {
+            x: 2000.0,
+            y: 300.5,
+            output: Some("TEST-1".into()),
+            output_x: 80.0,
+            output_y: 300.5,
+        }
+    );
+
+    // Surfaces are gone afterwards, so the query can be repeated
+    assert_eq!(whydotool.pointer_position().unwrap().x, 2000.0);
+}
+
+#[test]
+fn pointer_position_needs_layer_shell() {
+    let (_server, mut whydotool) = connect(ServerConfig {
+        layer_shell: false,
+        ..ServerConfig::default()
+    });
+    assert!(matches!(
+        whydotool.pointer_position(),
+        Err(Error::ProtocolUnsupported(_))
+    ));
+
+    let (_server, mut whydotool) = connect(ServerConfig {
+        pointer: (-10.0, -10.0),
+        ..ServerConfig::default()
+    });
+    assert!(matches!(
+        whydotool.pointer_position(),
+        Err(Error::UnknownPointerPosition)
+    ));
+}
+
+#[test]
+fn pointer_requests_end_with_frame() {
+    let (server, mut whydotool) = connect(ServerConfig::default());
+
+    let pointer = whydotool.virtual_pointer().unwrap();
+    pointer.motion(10.0, -5.0).unwrap();
+    pointer.button(BTN_LEFT, ButtonState::Pressed).unwrap();
+    pointer.scroll(0.0, 15.0).unwrap();
+    whydotool.roundtrip().unwrap();
+
+    assert_eq!(
+        server.requests(),
+        [
+            Request::Motion { dx: 10.0, dy: -5.0 },
+            Request::Frame,
+            Request::Button {
+                button: BTN_LEFT,
+                pressed: true,
+            },
+            Request::Frame,
+            Request::Axis {
+                axis: Axis::VerticalScroll,
+                value: 15.0,
+            },
+            Request::Frame,
+        ]
+    );
+}
+
+#[cfg(not(feature = "portals"))]
+#[test]
+fn missing_protocols() {
+    let (_server, whydotool) = connect(ServerConfig {
+        virtual_keyboard: false,
+        virtual_pointer: false,
+        ..ServerConfig::default()
+    });
+
+    assert!(matches!(
+        whydotool.virtual_keyboard(),
+        Err(Error::ProtocolUnsupported(_))
+    ));
+    assert!(matches!(
+        whydotool.virtual_pointer(),
+        Err(Error::ProtocolUnsupported(_))
+    ));
+}